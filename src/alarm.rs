@@ -1,7 +1,9 @@
 use std::collections::HashMap;
+use std::sync::Arc;
 use chrono::{DateTime, Utc};
-use serde_derive::Serialize;
+use serde_derive::{Deserialize, Serialize};
 use crate::config::alarm_templates::AlarmTemplateReceiver;
+use crate::config::general::AlarmPriority;
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Address {
@@ -38,9 +40,180 @@ pub struct MailData {
 
 #[derive(Debug, Clone, Serialize)]
 pub struct DmeData {
+    /// `POCSAG1200`, `ZVEI1`, `FMS`, ... for multimon-ng input, empty for DME receivers
+    pub(crate) kind: String,
+    /// FMS status (`0`-`9`, `A`-`F`) of a vehicle telegram, empty otherwise
+    pub(crate) status: String,
     pub(crate) date: String,
     pub(crate) ric: String,
+    /// `A`-`D`, empty if the receiver sends none
+    pub(crate) sub_address: String,
     pub(crate) content: String,
+    /// all RICs (with sub-address) of the alarm, see `aggregation_seconds`
+    pub(crate) rics: Vec<String>,
+}
+
+/// What a mail parser recognized. Missing fields and warnings show early when
+/// a Leitstelle changed the layout of its alarm mails.
+#[derive(Debug, Clone, Serialize, Default)]
+pub struct ParseReport {
+    pub found: Vec<String>,
+    pub missing: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+impl ParseReport {
+    pub fn field(&mut self, name: &str, present: bool) {
+        if present {
+            self.found.push(name.to_string());
+        } else {
+            self.missing.push(name.to_string());
+        }
+    }
+
+    pub fn warn(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+}
+
+/// Hydrant near the alarm address, see `Enrichment`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Hydrant {
+    pub kind: String,
+    pub diameter: String,
+    pub reference: String,
+    pub lat: f64,
+    pub lon: f64,
+    pub distance_m: f64,
+}
+
+impl Hydrant {
+    /// e.g. `45 m, Unterflur DN100`
+    pub fn summary(&self) -> String {
+        let details = [self.kind.as_str(), self.diameter.as_str()]
+            .iter()
+            .filter(|value| !value.is_empty())
+            .copied()
+            .collect::<Vec<_>>()
+            .join(" ");
+        if details.is_empty() {
+            format!("{:.0} m", self.distance_m)
+        } else {
+            format!("{:.0} m, {}", self.distance_m, details)
+        }
+    }
+}
+
+/// Way from the station to the alarm address, see `Router`.
+#[derive(Debug, Clone, Serialize)]
+pub struct Route {
+    pub distance_m: f64,
+    pub duration_s: f64,
+    /// `osrm`, `graph` or `estimate`
+    pub source: String,
+    /// `[lat, lon]` points from the station to the address
+    pub geometry: Vec<[f64; 2]>,
+}
+
+impl Route {
+    /// e.g. `4,2 km, ca. 7 min`
+    pub fn summary(&self) -> String {
+        let distance = format!("{:.1}", self.distance_m / 1000.0).replace('.', ",");
+        let minutes = (self.duration_s / 60.0).ceil().max(1.0);
+        format!("{} km, ca. {:.0} min", distance, minutes)
+    }
+}
+
+/// Entry of the object database (Einsatzplan, BMA Laufkarte, contacts).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ObjectInfo {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub street: String,
+    #[serde(default)]
+    pub city: String,
+    #[serde(default)]
+    pub einsatzplan: String,
+    #[serde(default)]
+    pub laufkarte: String,
+    #[serde(default)]
+    pub info: String,
+    #[serde(default)]
+    pub contacts: Vec<Contact>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Contact {
+    pub name: String,
+    #[serde(default)]
+    pub role: String,
+    #[serde(default)]
+    pub phone: String,
+}
+
+impl Contact {
+    /// e.g. `Max Mustermann (Hausmeister) 0123 456789`
+    pub fn summary(&self) -> String {
+        let mut summary = self.name.clone();
+        if !self.role.is_empty() {
+            summary.push_str(&format!(" ({})", self.role));
+        }
+        if !self.phone.is_empty() {
+            summary.push_str(&format!(" {}", self.phone));
+        }
+        summary
+    }
+}
+
+/// Catalogue entry of the alarm's Stichwort, see `StichwortCatalogue`.
+#[derive(Debug, Clone, Serialize)]
+pub struct StichwortInfo {
+    pub keyword: String,
+    pub title: String,
+    pub category: String,
+    pub priority: AlarmPriority,
+    pub color: String,
+}
+
+/// Entry of the vehicle status table, see `VehicleStatus`.
+#[derive(Debug, Clone, Serialize)]
+pub struct VehicleState {
+    /// FMS id
+    pub id: String,
+    pub name: String,
+    /// FMS status `1`-`9`
+    pub status: u8,
+    pub status_text: String,
+    /// status 1 or 2
+    pub available: bool,
+    /// source of the last telegram
+    pub source: String,
+    pub since: DateTime<Utc>,
+}
+
+/// File attached to the alarm (mail attachment like an alarm fax PDF, Einsatzplan, ...).
+/// The content is not serialized and shared between the clones of the alarm,
+/// `path` is set once it was written to disk.
+#[derive(Clone, Serialize)]
+pub struct Attachment {
+    pub name: String,
+    pub mime_type: String,
+    pub size: usize,
+    pub path: Option<String>,
+    #[serde(skip)]
+    pub data: Arc<[u8]>,
+}
+
+impl std::fmt::Debug for Attachment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Attachment")
+            .field("name", &self.name)
+            .field("mime_type", &self.mime_type)
+            .field("size", &self.size)
+            .field("path", &self.path)
+            .finish()
+    }
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -48,6 +221,7 @@ pub struct Alarm {
     pub id: String,
     pub origin: String,
     pub title: String,
+    pub stichwort: String,
     pub text: String,
     pub time: DateTime<Utc>,
     pub address: Address,
@@ -62,6 +236,14 @@ pub struct Alarm {
     pub alarm_sources: Vec<String>,
     pub mail_data: MailData,
     pub dme_data: DmeData,
+    pub attachments: Vec<Attachment>,
+    pub parse_report: ParseReport,
+    pub hydrants: Vec<Hydrant>,
+    pub object_info: Option<ObjectInfo>,
+    pub route: Option<Route>,
+    pub stichwort_info: Option<StichwortInfo>,
+    /// Vehicle status table when the alarm arrived.
+    pub vehicle_status: Vec<VehicleState>,
 }
 
 impl Address {
@@ -115,6 +297,7 @@ impl Alarm {
             id: "".to_string(),
             origin: "".to_string(),
             title: "".to_string(),
+            stichwort: "".to_string(),
             text: "".to_string(),
             time: Utc::now(),
             address: Address::new(),  // Use Address::new() here
@@ -135,10 +318,21 @@ impl Alarm {
                 date: 0,
             },
             dme_data: DmeData {
+                kind: "".to_string(),
+                status: "".to_string(),
                 date: "".to_string(),
                 ric: "".to_string(),
+                sub_address: "".to_string(),
                 content: "".to_string(),
+                rics: vec![],
             },
+            attachments: vec![],
+            parse_report: ParseReport::default(),
+            hydrants: vec![],
+            object_info: None,
+            route: None,
+            stichwort_info: None,
+            vehicle_status: vec![],
         }
     }
 
@@ -155,6 +349,20 @@ impl Alarm {
         self.title = title;
     }
 
+    pub fn set_stichwort(&mut self, stichwort: String) {
+        self.stichwort = stichwort;
+    }
+
+    /// Names of the vehicles with status 1 or 2.
+    pub fn available_vehicles(&self) -> Vec<String> {
+        self.vehicle_status.iter().filter(|state| state.available).map(|state| state.name.clone()).collect()
+    }
+
+    /// Loud unless the Stichwort catalogue says otherwise.
+    pub fn priority(&self) -> AlarmPriority {
+        self.stichwort_info.as_ref().map(|info| info.priority).unwrap_or_default()
+    }
+
     pub fn set_text(&mut self, text: String) {
         self.text = text;
     }
@@ -207,6 +415,10 @@ impl Alarm {
         self.dme_data = dme_data;
     }
 
+    pub fn add_attachment(&mut self, attachment: Attachment) {
+        self.attachments.push(attachment);
+    }
+
     pub fn add_template_name(&mut self, name: String) {
         self.template_names.push(name);
     }
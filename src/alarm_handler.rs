@@ -12,15 +12,30 @@ use crate::apis::telegram::Telegram;
 use crate::apis::typst::{Typst};
 use crate::config::alarm_templates::AlarmTemplates;
 use crate::config::general::{ApiType, GeneralConfig};
+use crate::enrichment::Enrichment;
+use crate::geocoder::Geocoder;
+use crate::routing::Router;
+use crate::stichwort::StichwortCatalogue;
+use crate::vehicle_status::VehicleStatus;
 use log::{debug, error, info, warn};
+use tokio::time::{timeout_at, Instant};
+
+/// Geocoding and routing together may delay the dispatch of an alarm by at most this.
+pub const LOOKUP_DEADLINE: std::time::Duration = std::time::Duration::from_secs(3);
 
 pub struct AlarmHandler {
     // channel to send and receive alarms
     recv_alarms: flume::Receiver<Alarm>,
     apis: Arc<Mutex<HashMap<String, Box<dyn Api>>>>,
     alarm_templates: AlarmTemplates,
-    last_alarms: Arc<Mutex<Vec<Alarm>>>, // Change to Arc<Mutex<>> for shared mutable access
-    config: GeneralConfig
+    /// the previous alarm, new alarms are compared against it
+    last_alarm: Arc<Mutex<Option<Alarm>>>,
+    config: GeneralConfig,
+    geocoder: Option<Arc<Geocoder>>,
+    enrichment: Option<Arc<Enrichment>>,
+    router: Option<Arc<Router>>,
+    stichwoerter: Arc<StichwortCatalogue>,
+    vehicle_status: VehicleStatus,
 }
 
 #[derive(PartialEq, Debug)]
@@ -31,7 +46,7 @@ enum AlarmType {
 }
 
 impl AlarmHandler {
-    pub fn new(recv_alarms: flume::Receiver<Alarm>, config: GeneralConfig, alarm_templates: AlarmTemplates) -> Self {
+    pub fn new(recv_alarms: flume::Receiver<Alarm>, config: GeneralConfig, alarm_templates: AlarmTemplates, geocoder: Option<Geocoder>, enrichment: Option<Enrichment>, router: Option<Router>, stichwoerter: StichwortCatalogue) -> Self {
         let mut apis_map = HashMap::new();
         for api_config in config.clone().apis {
             let name = api_config.name.clone();
@@ -52,11 +67,22 @@ impl AlarmHandler {
             recv_alarms,
             apis,
             alarm_templates,
-            last_alarms: Arc::new(Mutex::new(Vec::new())),
+            last_alarm: Arc::new(Mutex::new(None)),
             config,
+            geocoder: geocoder.map(Arc::new),
+            enrichment: enrichment.map(Arc::new),
+            router: router.map(Arc::new),
+            stichwoerter: Arc::new(stichwoerter),
+            vehicle_status: VehicleStatus::default(),
         }
     }
 
+    /// Vehicle status table copied into every alarm.
+    pub fn with_vehicle_status(mut self, vehicle_status: VehicleStatus) -> Self {
+        self.vehicle_status = vehicle_status;
+        self
+    }
+
     pub async fn check_api_connections(&self) {
         let apis_lock = self.apis.lock().await;
         if apis_lock.is_empty() {
@@ -76,50 +102,43 @@ impl AlarmHandler {
         let recv_alarms = self.recv_alarms.clone();
         let apis = self.apis.clone();
         let alarm_templates = self.alarm_templates.clone();
-        let last_alarms = self.last_alarms.clone();
+        let last_alarm = self.last_alarm.clone();
         let config = self.config.clone();
+        let geocoder = self.geocoder.clone();
+        let enrichment = self.enrichment.clone();
+        let router = self.router.clone();
+        let stichwoerter = self.stichwoerter.clone();
+        let vehicle_status = self.vehicle_status.clone();
 
         // Use tokio::spawn to create an async task
         tokio::spawn(async move {
             loop {
-                match recv_alarms.recv() {
+                match recv_alarms.recv_async().await {
                     Ok(mut alarm) => {
                         debug!("{:?}", alarm);
                         info!("AlarmHandler received alarm: {}", alarm.title);
 
-                        // apply default template
-                        match alarm_templates.templates.get("default") {
-                            Some(template) => {
-                                for (api_name, receiver) in template.apis.clone() {
-                                    debug!("Applying default template for {}", api_name);
-                                    alarm.apply_template(api_name.clone(), receiver);
-                                }
-                            },
-                            None => {
-                                error!("Default template not found");
-                                break;
-                            }
-                        };
+                        alarm.vehicle_status = vehicle_status.snapshot();
+                        stichwoerter.apply(&mut alarm);
+                        let deadline = Instant::now() + LOOKUP_DEADLINE;
+                        if let Some(geocoder) = &geocoder {
+                            geocode_alarm(&mut alarm, geocoder, deadline).await;
+                        }
+                        if let Some(enrichment) = &enrichment {
+                            enrichment.enrich(&mut alarm);
+                        }
+                        if let Some(router) = &router {
+                            route_alarm(&mut alarm, router, deadline).await;
+                        }
 
-                        // apply remaining templates from alarm
-                        for template_name in alarm.template_names.clone() {
-                            match alarm_templates.templates.get(&template_name) {
-                                Some(template) => {
-                                    for (api_name, receiver) in template.apis.clone() {
-                                        debug!("Applying template {} for {}", template_name, api_name);
-                                        alarm.apply_template(api_name.clone(), receiver);
-                                    }
-                                },
-                                None => {
-                                    warn!("Template {} not found", template_name);
-                                    continue; // Changed break to continue to prevent exiting the loop
-                                }
-                            };
+                        if let Err(e) = apply_templates(&mut alarm, &alarm_templates) {
+                            error!("{}", e);
+                            break;
                         }
 
                         let alarm_type = {
-                            let last_alarms_lock = last_alarms.lock().await;
-                            if let Some(last_alarm) = last_alarms_lock.last() {
+                            let last_alarm_lock = last_alarm.lock().await;
+                            if let Some(last_alarm) = last_alarm_lock.as_ref() {
                                 compare_alarms(&alarm, last_alarm, &config)
                             } else {
                                 AlarmType::FirstAlarm
@@ -195,9 +214,8 @@ impl AlarmHandler {
                             info!("Webhook dispatch is disabled by general config (general.alarm = false)");
                         }
 
-                        // Update last_alarms after processing
-                        let mut last_alarms_lock = last_alarms.lock().await;
-                        last_alarms_lock.push(alarm);
+                        // Update last_alarm after processing
+                        *last_alarm.lock().await = Some(alarm);
                     },
                     Err(e) => {
                         error!("Error receiving alarm: {}", e);
@@ -209,6 +227,76 @@ impl AlarmHandler {
     }
 }
 
+/// Fills missing coordinates from the address, gives up at `deadline`.
+pub async fn geocode_alarm(alarm: &mut Alarm, geocoder: &Geocoder, deadline: Instant) {
+    if alarm.address.coords.lat.is_some() && alarm.address.coords.lon.is_some() {
+        return;
+    }
+
+    match timeout_at(deadline, geocoder.geocode(&alarm.address)).await {
+        Err(_) => warn!("Geocoding von '{}, {}' abgebrochen, Alarm wird ohne Koordinaten gesendet", alarm.address.street, alarm.address.city),
+        Ok(Some(coords)) => {
+            info!("Adresse '{}, {}' geocodiert: {:?}", alarm.address.street, alarm.address.city, coords);
+            alarm.address.set_coords(coords);
+        }
+        Ok(None) => {
+            if !alarm.address.street.trim().is_empty() {
+                warn!("Adresse '{}, {}' konnte nicht geocodiert werden", alarm.address.street, alarm.address.city);
+            }
+        }
+    }
+}
+
+/// Adds the route from the station if the alarm has coordinates, the
+/// straight line estimate if routing does not finish until `deadline`.
+pub async fn route_alarm(alarm: &mut Alarm, router: &Router, deadline: Instant) {
+    if let (Some(lat), Some(lon)) = (alarm.address.coords.lat, alarm.address.coords.lon) {
+        let route = match timeout_at(deadline, router.route(lat, lon)).await {
+            Ok(route) => route,
+            Err(_) => {
+                warn!("Routing abgebrochen, Anfahrt wird geschätzt");
+                router.estimate(lat, lon)
+            }
+        };
+        info!("Anfahrt: {} ({})", route.summary(), route.source);
+        alarm.route = Some(route);
+    }
+}
+
+/// Applies the default template and all templates named by the alarm.
+pub fn apply_templates(alarm: &mut Alarm, alarm_templates: &AlarmTemplates) -> Result<(), String> {
+    // apply default template
+    match alarm_templates.templates.get("default") {
+        Some(template) => {
+            for (api_name, receiver) in template.apis.clone() {
+                debug!("Applying default template for {}", api_name);
+                alarm.apply_template(api_name.clone(), receiver);
+            }
+        },
+        None => {
+            return Err("Default template not found".to_string());
+        }
+    };
+
+    // apply remaining templates from alarm
+    for template_name in alarm.template_names.clone() {
+        match alarm_templates.templates.get(&template_name) {
+            Some(template) => {
+                for (api_name, receiver) in template.apis.clone() {
+                    debug!("Applying template {} for {}", template_name, api_name);
+                    alarm.apply_template(api_name.clone(), receiver);
+                }
+            },
+            None => {
+                warn!("Template {} not found", template_name);
+                continue;
+            }
+        };
+    }
+
+    Ok(())
+}
+
 // Move compare_alarms to a standalone function
 fn compare_alarms(new_alarm: &Alarm, old_alarm: &Alarm, config: &GeneralConfig) -> AlarmType {
     let time_diff = new_alarm.time.signed_duration_since(old_alarm.time);
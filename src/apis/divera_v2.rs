@@ -1,6 +1,7 @@
 use async_trait::async_trait;
 use crate::alarm::Alarm;
 use crate::apis::Api;
+use crate::config::general::AlarmPriority;
 use log::{debug, info};
 use reqwest::Client;
 use serde_json::json;
@@ -11,6 +12,30 @@ pub struct DiveraV2 {
     pub api_key: String,
 }
 
+impl DiveraV2 {
+    /// Sets the FMS status of a Divera vehicle.
+    pub async fn set_vehicle_status(&self, divera_id: u64, status: u8) -> Result<(), String> {
+        let client = Client::new();
+        let url = format!(
+            "https://app.divera247.com/api/v2/using-vehicles/set-status/{}?accesskey={}",
+            divera_id, self.api_key
+        );
+        let res = client
+            .post(url)
+            .json(&json!({ "status": status }))
+            .send()
+            .await
+            .map_err(|err| format!("Request error: {}", err))?;
+
+        let status = res.status();
+        if !status.is_success() {
+            let body = res.text().await.unwrap_or_default();
+            return Err(format!("HTTP {} - {}", status, body));
+        }
+        Ok(())
+    }
+}
+
 #[async_trait]
 impl Api for DiveraV2 {
     async fn trigger_alarm<'a>(&'a self, alarm: &'a Alarm) -> Result<(), String> {
@@ -46,12 +71,44 @@ impl Api for DiveraV2 {
             text.push_str(&format!("\n\nhttps://maps.apple.com/?q={},{}", lat, lng));
         }
 
+        if let Some(route) = &alarm.route {
+            text.push_str(&format!("\n\nAnfahrt: {}", route.summary()));
+        }
+
+        let available = alarm.available_vehicles();
+        if !available.is_empty() {
+            text.push_str(&format!("\n\nEinsatzbereit: {}", available.join(", ")));
+        }
+
+        if !alarm.hydrants.is_empty() {
+            text.push_str("\n\nHydranten:");
+            for hydrant in &alarm.hydrants {
+                text.push_str(&format!("\n- {}", hydrant.summary()));
+            }
+        }
+
+        if let Some(object) = &alarm.object_info {
+            text.push_str(&format!("\n\nObjekt: {}", object.name));
+            if !object.info.is_empty() {
+                text.push_str(&format!("\n{}", object.info));
+            }
+            if !object.einsatzplan.is_empty() {
+                text.push_str(&format!("\nEinsatzplan: {}", object.einsatzplan));
+            }
+            if !object.laufkarte.is_empty() {
+                text.push_str(&format!("\nLaufkarte: {}", object.laufkarte));
+            }
+            for contact in &object.contacts {
+                text.push_str(&format!("\nKontakt: {}", contact.summary()));
+            }
+        }
+
         let client = Client::new();
         let req_body = json!({
             "accesskey": self.api_key,
             "Alarm": {
                 "foreign_id": alarm.id,
-                "priority": true,
+                "priority": alarm.priority() == AlarmPriority::Loud,
                 "title": alarm.title,
                 "text": text,
                 "address": alarm.address.street,
@@ -1,6 +1,7 @@
 use async_trait::async_trait;
-use crate::alarm::Alarm;
+use crate::alarm::{Alarm, Attachment};
 use crate::apis::Api;
+use crate::config::general::AlarmPriority;
 use reqwest::Client;
 use log::{error, info};
 use serde_json::Value;
@@ -32,6 +33,27 @@ fn escape_markdown_v2(text: &str) -> String {
         .replace('!', "\\!")
 }
 
+/// Builds a multipart/form-data body for `sendDocument`.
+fn document_form(chat_id: &str, attachment: &Attachment, boundary: &str) -> Vec<u8> {
+    let file_name = attachment.name.replace(['"', '\r', '\n'], "_");
+    let mut body = Vec::new();
+    body.extend_from_slice(
+        format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"chat_id\"\r\n\r\n{chat_id}\r\n"
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(
+        format!(
+            "--{boundary}\r\nContent-Disposition: form-data; name=\"document\"; filename=\"{file_name}\"\r\nContent-Type: {}\r\n\r\n",
+            attachment.mime_type
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(&attachment.data);
+    body.extend_from_slice(format!("\r\n--{boundary}--\r\n").as_bytes());
+    body
+}
 
 #[async_trait]
 impl Api for Telegram {
@@ -70,6 +92,36 @@ impl Api for Telegram {
                 text.push_str(&format!("\n\n[Apple Maps]({})\n", link_url));
             }
 
+            if let Some(route) = &alarm.route {
+                text.push_str(&format!("\n*Anfahrt:* {}\n", escape_markdown_v2(&route.summary())));
+            }
+
+            if !alarm.hydrants.is_empty() {
+                text.push_str("\n*Hydranten:*");
+                for hydrant in &alarm.hydrants {
+                    text.push_str(&format!("\n\\- {}", escape_markdown_v2(&hydrant.summary())));
+                }
+                text.push('\n');
+            }
+
+            if let Some(object) = &alarm.object_info {
+                text.push_str(&format!("\n*Objekt:* {}", escape_markdown_v2(&object.name)));
+                for (label, value) in [("", &object.info), ("Einsatzplan: ", &object.einsatzplan), ("Laufkarte: ", &object.laufkarte)] {
+                    if !value.is_empty() {
+                        text.push_str(&format!("\n{}", escape_markdown_v2(&format!("{}{}", label, value))));
+                    }
+                }
+                for contact in &object.contacts {
+                    text.push_str(&format!("\n{}", escape_markdown_v2(&format!("Kontakt: {}", contact.summary()))));
+                }
+                text.push('\n');
+            }
+
+            let available = alarm.available_vehicles();
+            if !available.is_empty() {
+                text.push_str(&format!("\n*Einsatzbereit:* {}\n", escape_markdown_v2(&available.join(", "))));
+            }
+
             let alarm_einheiten = alarm.units.iter().map(|unit| escape_markdown_v2(unit)).collect::<Vec<String>>().join("\n");
 
             text.push_str(&alarm_einheiten);
@@ -79,7 +131,8 @@ impl Api for Telegram {
             let payload = serde_json::json!({
                 "chat_id": receiver,
                 "text": text,
-                "parse_mode": "MarkdownV2"
+                "parse_mode": "MarkdownV2",
+                "disable_notification": alarm.priority() == AlarmPriority::Silent
             });
 
             // 4. Send the POST request
@@ -97,12 +150,37 @@ impl Api for Telegram {
                 }
                 Err(err) => error!("Network error while contacting Telegram: {}", err),
             }
+
+            // 5. Forward attachments (alarm fax, maps) as documents
+            for attachment in &alarm.attachments {
+                let boundary = format!("alarm-server-{}", chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default());
+                let endpoint = format!("https://api.telegram.org/bot{}/sendDocument", self.bot_token);
+
+                match client
+                    .post(&endpoint)
+                    .header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+                    .body(document_form(&receiver, attachment, &boundary))
+                    .send()
+                    .await
+                {
+                    Ok(res) if res.status().is_success() => {
+                        info!("Attachment {} sent to: {}", attachment.name, receiver);
+                    }
+                    Ok(res) => {
+                        let status = res.status();
+                        let response_body = res.text().await.unwrap_or_else(|_| "Unknown error".to_string());
+                        error!("Telegram API Error ({}) sending {}: {}", status, attachment.name, response_body);
+                    }
+                    Err(err) => error!("Network error while sending attachment to Telegram: {}", err),
+                }
+            }
         }
 
         Ok(())
     }
 
     async fn update_alarm<'a>(&'a self, _alarm: &'a Alarm) -> Result<(), String> {
+        // attachments were sent with the first alarm message, updates send nothing again
         info!("Telegram API: Updating alarm with key: {}", self.bot_token);
         Ok(())
     }
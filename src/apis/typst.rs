@@ -1,13 +1,13 @@
 use async_trait::async_trait;
 use chrono::Local;
-use log::info;
+use log::{info, warn};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use serde_json::json;
 use staticmap::{StaticMapBuilder};
-use staticmap::tools::{Color, LineBuilder};
-use crate::alarm::Alarm;
+use staticmap::tools::{CircleBuilder, Color, LineBuilder};
+use crate::alarm::{Alarm, Hydrant, Route};
 use crate::apis::Api;
 
 pub struct Typst {
@@ -82,15 +82,37 @@ fn render_alarm_pdf(
     let json_path = output_dir.join(format!("{}.json", base_name));
     let pdf_path = output_dir.join(format!("{}.pdf", base_name));
     let map_path = output_dir.join(format!("{}_map.png", base_name));
+    let route_map_path = output_dir.join(format!("{}_route.png", base_name));
 
     // 1. Generate the OSM Map Screenshot (100m x 100m)
     // Accessing lat/lon through alarm.address.coords as per your snippet
     let lat = alarm.address.coords.lat.ok_or("Alarm missing latitude")?;
     let lon = alarm.address.coords.lon.ok_or("Alarm missing longitude")?;
 
-    generate_static_map(lat, lon, &map_path)?;
+    generate_static_map(lat, lon, &alarm.hydrants, &map_path)?;
 
-    // 2. Prepare JSON for Typst
+    // Overview map with the route from the station, optional for the template
+    if let Some(route) = &alarm.route {
+        if let Err(e) = generate_route_map(route, &route_map_path) {
+            warn!("Typst API: {}", e);
+        }
+    }
+
+    // 2. Write attachments next to the JSON so the template can embed them
+    let mut alarm = alarm;
+    let mut attachment_paths = vec![];
+    for (idx, attachment) in alarm.attachments.iter_mut().enumerate() {
+        let file_name = format!("{}_att{}_{}", base_name, idx + 1, sanitize_attachment_name(&attachment.name));
+        let attachment_path = output_dir.join(&file_name);
+        if let Err(e) = fs::write(&attachment_path, &attachment.data) {
+            warn!("Typst API: attachment {} skipped: {}", attachment.name, e);
+            continue;
+        }
+        attachment.path = Some(file_name);
+        attachment_paths.push(attachment_path);
+    }
+
+    // 3. Prepare JSON for Typst
     let json_value = json!(alarm);
 
     let json_data = serde_json::to_string_pretty(&json_value)
@@ -99,7 +121,7 @@ fn render_alarm_pdf(
     fs::write(&json_path, json_data)
         .map_err(|e| format!("Failed to write JSON: {}", e))?;
 
-    // 3. Compile Typst
+    // 4. Compile Typst
     let output = Command::new(&typst_bin)
         .arg("compile")
         .arg("--input")
@@ -117,12 +139,16 @@ fn render_alarm_pdf(
     // Clean up temporary files
     let _ = fs::remove_file(&json_path);
     let _ = fs::remove_file(&map_path);
+    let _ = fs::remove_file(&route_map_path);
+    for attachment_path in attachment_paths {
+        let _ = fs::remove_file(attachment_path);
+    }
 
     Ok(pdf_path)
 }
 
-/// Generates a 100m x 100m map centered on the coordinates
-fn generate_static_map(lat: f64, lon: f64, save_path: &Path) -> Result<(), String> {
+/// Generates a 100m x 100m map centered on the coordinates, nearby hydrants are marked
+fn generate_static_map(lat: f64, lon: f64, hydrants: &[Hydrant], save_path: &Path) -> Result<(), String> {
 
     let mut map = StaticMapBuilder::default()
         .width(800)
@@ -161,6 +187,18 @@ fn generate_static_map(lat: f64, lon: f64, save_path: &Path) -> Result<(), Strin
     map.add_tool(line1);
     map.add_tool(line2);
 
+    // Nearby hydrants as blue dots
+    for hydrant in hydrants {
+        let circle = CircleBuilder::default()
+            .lat_coordinate(hydrant.lat)
+            .lon_coordinate(hydrant.lon)
+            .color(Color::new(true, 0, 90, 255, 255))
+            .radius(7.0)
+            .build()
+            .map_err(|e| format!("Failed to build hydrant marker: {}", e))?;
+        map.add_tool(circle);
+    }
+
     match map.save_png(save_path) {
         Ok(_) => info!("Map image saved to {}", save_path.display()),
         Err(e) => return Err(format!("Failed to save map image: {}", e)),
@@ -169,7 +207,45 @@ fn generate_static_map(lat: f64, lon: f64, save_path: &Path) -> Result<(), Strin
     Ok(())
 }
 
-fn sanitize_file_component(input: &str) -> String {
+/// Overview map of the route, the zoom is chosen to fit the whole route
+fn generate_route_map(route: &Route, save_path: &Path) -> Result<(), String> {
+    if route.geometry.len() < 2 {
+        return Err("Route has no geometry".to_string());
+    }
+
+    let mut map = StaticMapBuilder::default()
+        .width(800)
+        .height(400)
+        .padding((20, 20))
+        .url_template("https://tile.openstreetmap.de/{z}/{x}/{y}.png")
+        .build()
+        .map_err(|e| format!("Failed to initialize route map builder: {}", e))?;
+
+    let line = LineBuilder::default()
+        .lat_coordinates(route.geometry.iter().map(|point| point[0]).collect::<Vec<_>>())
+        .lon_coordinates(route.geometry.iter().map(|point| point[1]).collect::<Vec<_>>())
+        .width(5.0)
+        .color(Color::new(true, 0, 90, 255, 255))
+        .build()
+        .map_err(|e| format!("Failed to build route line: {}", e))?;
+    map.add_tool(line);
+
+    let target = route.geometry[route.geometry.len() - 1];
+    let marker = CircleBuilder::default()
+        .lat_coordinate(target[0])
+        .lon_coordinate(target[1])
+        .color(Color::new(true, 255, 0, 0, 255))
+        .radius(8.0)
+        .build()
+        .map_err(|e| format!("Failed to build route marker: {}", e))?;
+    map.add_tool(marker);
+
+    map.save_png(save_path).map_err(|e| format!("Failed to save route map: {}", e))?;
+    info!("Route map saved to {}", save_path.display());
+    Ok(())
+}
+
+pub fn sanitize_file_component(input: &str) -> String {
     let mut out = String::with_capacity(input.len());
     for ch in input.chars() {
         match ch {
@@ -185,6 +261,18 @@ fn sanitize_file_component(input: &str) -> String {
     }
 }
 
+/// Like `sanitize_file_component`, but keeps the file extension.
+fn sanitize_attachment_name(name: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) => format!(
+            "{}.{}",
+            sanitize_file_component(stem),
+            sanitize_file_component(extension)
+        ),
+        None => sanitize_file_component(name),
+    }
+}
+
 // --- PUBLIC HELPER FUNCTIONS ---
 
 pub fn default_output_dir() -> PathBuf {
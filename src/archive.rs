@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use chrono::Local;
+use log::{debug, error, info};
+use crate::apis::typst::sanitize_file_component;
+use crate::config::general::ArchiveConfig;
+
+/// Stores raw alarm input (mails, serial frames) on disk for after-action
+/// reports and parser debugging. Every source gets its own subdirectory.
+#[derive(Clone)]
+pub struct Archive {
+    directory: PathBuf,
+    max_age: Option<Duration>,
+    max_files: usize,
+}
+
+impl Archive {
+    pub fn new(config: &ArchiveConfig) -> Self {
+        Self {
+            directory: PathBuf::from(&config.directory),
+            max_age: match config.max_age_days {
+                0 => None,
+                days => Some(Duration::from_secs(days * 24 * 60 * 60)),
+            },
+            max_files: config.max_files,
+        }
+    }
+
+    /// Writes `data` as `<directory>/<source>/<timestamp>_<alarm id>.<extension>`
+    /// and applies the retention rules afterwards. Errors are only logged,
+    /// archiving must never stop alarm handling. Returns the path of the file.
+    pub fn store(&self, source: &str, alarm_id: &str, extension: &str, data: &[u8]) -> Option<PathBuf> {
+        let source_dir = self.directory.join(sanitize_file_component(source));
+        if let Err(e) = fs::create_dir_all(&source_dir) {
+            error!("Could not create archive directory {}: {}", source_dir.display(), e);
+            return None;
+        }
+
+        let ts = Local::now().format("%Y%m%d_%H%M%S%.3f").to_string();
+        let id = if alarm_id.trim().is_empty() {
+            "no-id".to_string()
+        } else {
+            sanitize_file_component(alarm_id)
+        };
+        let path = source_dir.join(format!("{}_{}.{}", ts, id, extension));
+
+        match fs::write(&path, data) {
+            Ok(_) => info!("Raw message archived at {}", path.display()),
+            Err(e) => {
+                error!("Could not archive raw message at {}: {}", path.display(), e);
+                return None;
+            }
+        }
+
+        self.apply_retention(&source_dir);
+        Some(path)
+    }
+
+    /// Renames a file stored without alarm id (`<timestamp>_no-id.<extension>`)
+    /// after the alarm id, once the message was parsed.
+    pub fn attach_id(&self, path: &Path, alarm_id: &str) {
+        let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+        if alarm_id.trim().is_empty() || !file_name.contains("_no-id.") {
+            return;
+        }
+        let renamed = path.with_file_name(file_name.replacen("_no-id.", &format!("_{}.", sanitize_file_component(alarm_id)), 1));
+        match fs::rename(path, &renamed) {
+            Ok(_) => debug!("Archived message renamed to {}", renamed.display()),
+            Err(e) => error!("Could not rename archived message {}: {}", path.display(), e),
+        }
+    }
+
+    fn apply_retention(&self, source_dir: &Path) {
+        let entries = match fs::read_dir(source_dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("Could not read archive directory {}: {}", source_dir.display(), e);
+                return;
+            }
+        };
+
+        let mut files: Vec<(PathBuf, SystemTime)> = entries
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().map(|t| t.is_file()).unwrap_or(false))
+            .filter_map(|entry| {
+                let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+                Some((entry.path(), modified))
+            })
+            .collect();
+
+        // newest first
+        files.sort_by_key(|(_, modified)| std::cmp::Reverse(*modified));
+
+        let now = SystemTime::now();
+        for (idx, (path, modified)) in files.iter().enumerate() {
+            let too_old = match self.max_age {
+                Some(max_age) => now.duration_since(*modified).map(|age| age > max_age).unwrap_or(false),
+                None => false,
+            };
+            let too_many = self.max_files > 0 && idx >= self.max_files;
+
+            if too_old || too_many {
+                debug!("Removing archived message {}", path.display());
+                if let Err(e) = fs::remove_file(path) {
+                    error!("Could not remove archived message {}: {}", path.display(), e);
+                }
+            }
+        }
+    }
+}
@@ -11,7 +11,11 @@ pub struct AlarmSources {
 pub struct MailConfig {
     pub name: String,
     pub active: bool,
+    #[serde(default)]
+    pub protocol: MailProtocol,
+    #[serde(default)]
     pub user: String,
+    #[serde(default)]
     pub password: String,
     pub host: String,
     pub port: u16,
@@ -19,6 +23,11 @@ pub struct MailConfig {
     pub max_age: u64,
     pub alarm_sender: String,
     pub alarm_subject: String,
+    #[serde(default)]
+    pub rules: Vec<MailRule>,
+    /// authserv-ids of the own mail servers, only their `Authentication-Results` are trusted.
+    #[serde(default)]
+    pub authserv_ids: Vec<String>,
     pub alarm_template_keywords: HashMap<String, String>,
     pub mail_schema: String,
     pub stichwoerter: HashMap<String, String>,
@@ -26,15 +35,330 @@ pub struct MailConfig {
     pub polling: bool,
     pub polling_interval: u64,
     pub idle: bool,
+    #[serde(default)]
+    pub delete_after_fetch: bool,
+    #[serde(default)]
+    pub charset: Option<String>,
+    #[serde(default)]
+    pub plaintext_fields: PlaintextFields,
+    #[serde(default)]
+    pub table_schema: TableSchema,
 }
 
-#[derive(Deserialize)]
+/// Field mappings for the `Plaintext` parser. Each value is either a line key
+/// like `"Stichwort:"` (the rest of the line is the value) or `regex:` followed
+/// by an expression whose `value` group (or first group) is used.
+#[derive(Deserialize, Clone, Default)]
+pub struct PlaintextFields {
+    pub id: Option<String>,
+    pub title: Option<String>,
+    pub stichwort: Option<String>,
+    pub text: Option<String>,
+    pub street: Option<String>,
+    pub city: Option<String>,
+    pub object: Option<String>,
+    pub object_id: Option<String>,
+    pub info: Option<String>,
+    pub utm: Option<String>,
+    /// Line key with "lat, lon" or a regex with `lat` and `lon` groups.
+    pub coords: Option<String>,
+    /// Every match adds units, comma separated values are split.
+    pub units: Option<String>,
+}
+
+impl PlaintextFields {
+    pub fn is_empty(&self) -> bool {
+        [
+            &self.id, &self.title, &self.stichwort, &self.text, &self.street, &self.city,
+            &self.object, &self.object_id, &self.info, &self.utm, &self.coords, &self.units,
+        ]
+        .iter()
+        .all(|field| field.is_none())
+    }
+
+    /// Copy of `self` where every field set in `overrides` is replaced.
+    pub fn merged_with(&self, overrides: &PlaintextFields) -> PlaintextFields {
+        let pick = |base: &Option<String>, over: &Option<String>| over.clone().or_else(|| base.clone());
+        PlaintextFields {
+            id: pick(&self.id, &overrides.id),
+            title: pick(&self.title, &overrides.title),
+            stichwort: pick(&self.stichwort, &overrides.stichwort),
+            text: pick(&self.text, &overrides.text),
+            street: pick(&self.street, &overrides.street),
+            city: pick(&self.city, &overrides.city),
+            object: pick(&self.object, &overrides.object),
+            object_id: pick(&self.object_id, &overrides.object_id),
+            info: pick(&self.info, &overrides.info),
+            utm: pick(&self.utm, &overrides.utm),
+            coords: pick(&self.coords, &overrides.coords),
+            units: pick(&self.units, &overrides.units),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Default, PartialEq)]
+pub enum MailProtocol {
+    #[default]
+    #[serde(alias = "IMAP")]
+    Imap,
+    #[serde(alias = "POP3")]
+    Pop3,
+    #[serde(alias = "SMTP")]
+    Smtp,
+}
+
+/// Describes an html alarm mail made of `<table>` rows with the key in the
+/// first cell, used by the `Table` parser. Every field lists table keys, the
+/// first key present in the mail wins.
+#[derive(Deserialize, Clone)]
+pub struct TableSchema {
+    #[serde(default)]
+    pub id: Vec<String>,
+    /// Falls back to the (normalized) `stichwort` if none of the keys is present.
+    #[serde(default)]
+    pub title: Vec<String>,
+    /// Optional regex applied to the title, its first group is used (e.g. `\((.*)\)`).
+    pub title_pattern: Option<String>,
+    #[serde(default)]
+    pub stichwort: Vec<String>,
+    #[serde(default)]
+    pub text: Vec<String>,
+    #[serde(default)]
+    pub street: Vec<String>,
+    #[serde(default)]
+    pub city: Vec<String>,
+    #[serde(default)]
+    pub object: Vec<String>,
+    #[serde(default)]
+    pub object_id: Vec<String>,
+    #[serde(default)]
+    pub info: Vec<String>,
+    #[serde(default)]
+    pub utm: Vec<String>,
+    #[serde(default)]
+    pub coords: Vec<String>,
+    /// Order of the two numbers in the `coords` row.
+    #[serde(default)]
+    pub coords_order: CoordsOrder,
+    /// Key of the row after which every row key is a unit.
+    pub units_start: Option<String>,
+    /// Keys ending the unit section, the first one following `units_start` in the mail is used.
+    #[serde(default)]
+    pub units_end: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Default, PartialEq)]
+pub enum CoordsOrder {
+    #[default]
+    LatLon,
+    LonLat,
+}
+
+impl Default for TableSchema {
+    /// Layout of the secur.CAD alarm mail.
+    fn default() -> Self {
+        let keys = |keys: &[&str]| keys.iter().map(|k| k.to_string()).collect::<Vec<_>>();
+        Self {
+            id: keys(&["Auftragsnummer:"]),
+            title: keys(&["Notfallgeschehen:"]),
+            title_pattern: Some(r"\((.*)\)".to_string()),
+            stichwort: keys(&["Einsatzstichwort:"]),
+            text: keys(&["Sachverhalt:", "Objekt:"]),
+            street: keys(&["Strasse / Hs.-Nr.:", "Strasse:"]),
+            city: keys(&["PLZ / Ort:"]),
+            object: keys(&["Objekt:"]),
+            object_id: vec![],
+            info: keys(&["Info:"]),
+            utm: keys(&["UTM - Koordinaten:"]),
+            coords: keys(&["Geopositionen:"]),
+            coords_order: CoordsOrder::LonLat,
+            units_start: Some("Ressourcen".to_string()),
+            units_end: keys(&["Meldender des Hilfeersuchens", "Druckdatum:"]),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct MailRule {
+    pub name: Option<String>,
+    #[serde(default)]
+    pub senders: Vec<String>,
+    #[serde(default = "default_rule_subject")]
+    pub subject: String,
+    pub dkim_domain: Option<String>,
+    pub received: Option<String>,
+    pub mail_schema: Option<String>,
+}
+
+fn default_rule_subject() -> String {
+    "*".to_string()
+}
+
+/// Pager text rule, the rule with the highest `priority` that matches is
+/// applied (equal priorities in config order).
+#[derive(Deserialize, Clone)]
+pub struct TextRule {
+    pub name: Option<String>,
+    /// Regex with the named groups `stichwort`, `title`, `street`, `city`, `object`, `info` and `text`.
+    pub pattern: String,
+    #[serde(default)]
+    pub priority: i32,
+}
+
+#[derive(Deserialize, Clone)]
 pub struct SerialConfig {
     pub name: String,
     pub active: bool,
+    /// Device path, not needed if `usb` is set.
+    #[serde(default)]
     pub port: String,
+    /// Finds the port by USB ids, so it survives re-plugging into another socket.
+    #[serde(default)]
+    pub usb: Option<UsbConfig>,
+    /// Reads the frames from a serial device server instead of a local port.
+    #[serde(default)]
+    pub network: Option<NetworkConfig>,
+    /// Named pipe or file with decoder output (e.g. multimon-ng), `-` for stdin.
+    #[serde(default)]
+    pub pipe: Option<String>,
     pub delimiter: String,
+    /// Not needed for `network`.
+    #[serde(default)]
     pub baudrate: u32,
+    /// Stichwörter searched as whole words in the text, the longest match wins.
+    #[serde(default)]
     pub alarm_list: Vec<String>,
-    pub rics: HashMap<String, String>,
-}
\ No newline at end of file
+    /// Regex rules for the pager text, tried before `alarm_list`.
+    #[serde(default)]
+    pub text_rules: Vec<TextRule>,
+    /// RIC (optionally with sub-address, `1234567C`) to templates.
+    pub rics: HashMap<String, RicMapping>,
+    /// RICs arriving within this many seconds after the first one are one alarm, `0` disables.
+    #[serde(default)]
+    pub aggregation_seconds: u64,
+    /// Frame decoder, see `DME_PROTOCOLS`.
+    #[serde(default = "default_protocol")]
+    pub protocol: String,
+    #[serde(default)]
+    pub charset: Option<String>,
+    /// Regex for protocol `Regex`.
+    #[serde(default)]
+    pub frame_pattern: Option<String>,
+    /// First wait before reopening the port, doubled up to `reconnect_max_seconds`.
+    #[serde(default = "default_reconnect_min")]
+    pub reconnect_min_seconds: u64,
+    #[serde(default = "default_reconnect_max")]
+    pub reconnect_max_seconds: u64,
+    /// A frame without delimiter is complete after this pause, `0` waits forever.
+    #[serde(default = "default_frame_timeout")]
+    pub frame_timeout_ms: u64,
+    /// Longer frames are cut, `0` means unlimited.
+    #[serde(default = "default_max_frame_size")]
+    pub max_frame_size: usize,
+}
+
+fn default_protocol() -> String {
+    "Standard".to_string()
+}
+
+fn default_frame_timeout() -> u64 {
+    1000
+}
+
+fn default_max_frame_size() -> usize {
+    4096
+}
+
+fn default_reconnect_min() -> u64 {
+    1
+}
+
+fn default_reconnect_max() -> u64 {
+    60
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(untagged)]
+pub enum RicMapping {
+    Template(String),
+    Templates(Vec<String>),
+    Filtered {
+        templates: Vec<String>,
+        /// Only for these sub-addresses (`A`-`D`), empty means all.
+        #[serde(default)]
+        sub_addresses: Vec<String>,
+    },
+}
+
+impl RicMapping {
+    /// Templates for an alarm with `sub_address`, `None` if it is filtered out.
+    pub fn templates(&self, sub_address: &str) -> Option<Vec<String>> {
+        match self {
+            RicMapping::Template(template) => Some(vec![template.clone()]),
+            RicMapping::Templates(templates) => Some(templates.clone()),
+            RicMapping::Filtered { templates, sub_addresses } => {
+                let matches = sub_addresses.is_empty()
+                    || sub_addresses.iter().any(|sub| sub.trim().eq_ignore_ascii_case(sub_address));
+                matches.then(|| templates.clone())
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NetworkConfig {
+    pub mode: NetworkMode,
+    /// `host:port` to connect to (`TcpClient`) or to bind (`TcpListener`, `Udp`).
+    pub address: String,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub enum NetworkMode {
+    TcpClient,
+    TcpListener,
+    Udp,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct UsbConfig {
+    /// Vendor id in hex, e.g. `0403`.
+    pub vid: String,
+    /// Product id in hex, e.g. `6001`.
+    pub pid: String,
+    #[serde(default)]
+    pub serial_number: Option<String>,
+}
+
+impl UsbConfig {
+    /// `vid` and `pid` as numbers.
+    pub fn ids(&self) -> Result<(u16, u16), String> {
+        let parse = |value: &str| {
+            u16::from_str_radix(value.trim().trim_start_matches("0x"), 16).map_err(|_| format!("Invalid USB id '{}'", value))
+        };
+        Ok((parse(&self.vid)?, parse(&self.pid)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapping(value: serde_json::Value) -> RicMapping {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn ric_mapping_templates() {
+        assert_eq!(mapping(serde_json::json!("Vollalarm")).templates("C"), Some(vec!["Vollalarm".to_string()]));
+        assert_eq!(mapping(serde_json::json!(["Vollalarm", "DLK"])).templates(""), Some(vec!["Vollalarm".to_string(), "DLK".to_string()]));
+
+        let filtered = mapping(serde_json::json!({ "templates": ["Vollalarm"], "sub_addresses": ["A", " b "] }));
+        assert_eq!(filtered.templates("A"), Some(vec!["Vollalarm".to_string()]));
+        assert_eq!(filtered.templates("B"), Some(vec!["Vollalarm".to_string()]));
+        assert_eq!(filtered.templates("C"), None);
+        assert_eq!(filtered.templates(""), None);
+
+        let all = mapping(serde_json::json!({ "templates": ["Vollalarm"] }));
+        assert_eq!(all.templates("D"), Some(vec!["Vollalarm".to_string()]));
+    }
+}
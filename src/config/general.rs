@@ -1,4 +1,5 @@
-use serde_derive::Deserialize;
+use std::collections::HashMap;
+use serde_derive::{Deserialize, Serialize};
 
 #[derive(Deserialize, Debug, Clone)]
 pub struct GeneralConfig {
@@ -9,6 +10,130 @@ pub struct GeneralConfig {
     pub alarm: bool,
     #[serde(default)]
     pub delay: u64,
+    #[serde(default)]
+    pub archive: Option<ArchiveConfig>,
+    #[serde(default)]
+    pub geocoding: Option<GeocodingConfig>,
+    #[serde(default)]
+    pub enrichment: Option<EnrichmentConfig>,
+    #[serde(default)]
+    pub routing: Option<RoutingConfig>,
+    /// Stichwort catalogue, keys are the Stichwörter (e.g. `B2`, `THL 1`).
+    #[serde(default)]
+    pub stichwoerter: HashMap<String, StichwortConfig>,
+    /// Vehicles by FMS id (e.g. `0371721`), for the status table.
+    #[serde(default)]
+    pub vehicles: HashMap<String, VehicleConfig>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct VehicleConfig {
+    pub name: String,
+    /// Vehicle id in Divera, status changes are synced if set.
+    #[serde(default)]
+    pub divera_id: Option<u64>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StichwortConfig {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub priority: AlarmPriority,
+    /// Display colour, e.g. `#d32f2f`.
+    #[serde(default)]
+    pub color: Option<String>,
+    /// Templates applied to every alarm with this Stichwort.
+    #[serde(default)]
+    pub templates: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AlarmPriority {
+    Silent,
+    #[default]
+    Loud,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ArchiveConfig {
+    pub directory: String,
+    #[serde(default)]
+    pub max_age_days: u64,
+    #[serde(default)]
+    pub max_files: usize,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct GeocodingConfig {
+    /// CSV file with street, house number, postcode, city, lat and lon columns.
+    #[serde(default)]
+    pub dataset: Option<String>,
+    /// Base URL of a Nominatim compatible server, e.g. `http://localhost:8080`.
+    #[serde(default)]
+    pub nominatim_url: Option<String>,
+    #[serde(default = "default_request_timeout")]
+    pub timeout_seconds: u64,
+}
+
+fn default_request_timeout() -> u64 {
+    5
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct EnrichmentConfig {
+    /// GeoJSON (points) or CSV file with hydrants.
+    #[serde(default)]
+    pub hydrants: Option<String>,
+    #[serde(default = "default_hydrant_radius")]
+    pub hydrant_radius_m: f64,
+    #[serde(default = "default_max_hydrants")]
+    pub max_hydrants: usize,
+    /// JSON array of `ObjectInfo`.
+    #[serde(default)]
+    pub objects: Option<String>,
+}
+
+fn default_hydrant_radius() -> f64 {
+    150.0
+}
+
+fn default_max_hydrants() -> usize {
+    3
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct RoutingConfig {
+    pub station: StationConfig,
+    /// Base URL of an OSRM compatible server, e.g. `http://localhost:5000`.
+    #[serde(default)]
+    pub osrm_url: Option<String>,
+    /// GeoJSON file with the roads (LineStrings with OSM `highway`/`maxspeed`).
+    #[serde(default)]
+    pub graph: Option<String>,
+    #[serde(default = "default_average_speed")]
+    pub average_speed_kmh: f64,
+    #[serde(default = "default_detour_factor")]
+    pub detour_factor: f64,
+    #[serde(default = "default_request_timeout")]
+    pub timeout_seconds: u64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct StationConfig {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+fn default_average_speed() -> f64 {
+    40.0
+}
+
+fn default_detour_factor() -> f64 {
+    1.3
 }
 
 #[derive(Deserialize, Debug, Clone)]
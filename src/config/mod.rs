@@ -8,7 +8,11 @@ use std::path::Path;
 use serde::Deserialize;
 use crate::config::alarm_sources::AlarmSources;
 use crate::config::alarm_templates::AlarmTemplates;
-use crate::config::general::GeneralConfig;
+use crate::config::general::{ApiType, GeneralConfig};
+use crate::dme_decoder::{decoder_for, dme_charset};
+use crate::mail_filter::MailFilter;
+use crate::mail_parser::{parser_for, table_charset};
+use crate::pager_text::PagerText;
 
 pub struct Configs {
     pub alarm_sources: AlarmSources,
@@ -57,6 +61,68 @@ pub fn parse_configs() -> Result<Configs, Box<dyn Error>> {
     };
 
     // todo: validate configs (check if the api names are in the templates etc.)
+    validate_mail_sources(&alarm_sources)?;
+    validate_serial_sources(&alarm_sources)?;
+    validate_stichwoerter(&general, &alarm_templates)?;
+    validate_vehicles(&general)?;
 
     Ok(Configs{alarm_sources, alarm_templates, general})
-}
\ No newline at end of file
+}
+
+fn validate_mail_sources(alarm_sources: &AlarmSources) -> Result<(), Box<dyn Error>> {
+    for source in &alarm_sources.mail_sources {
+        let schemas = std::iter::once(&source.mail_schema)
+            .chain(source.rules.iter().filter_map(|rule| rule.mail_schema.as_ref()));
+        for schema in schemas {
+            parser_for(schema, source).map_err(|e| format!("Mail source '{}': {}", source.name, e))?;
+        }
+        table_charset(source.charset.as_deref()).map_err(|e| format!("Mail source '{}': {}", source.name, e))?;
+        MailFilter::new(source).map_err(|e| format!("Mail source '{}': {}", source.name, e))?;
+    }
+    Ok(())
+}
+
+fn validate_serial_sources(alarm_sources: &AlarmSources) -> Result<(), Box<dyn Error>> {
+    for source in &alarm_sources.serial_sources {
+        decoder_for(source).map_err(|e| format!("Serial source '{}': {}", source.name, e))?;
+        PagerText::new(source).map_err(|e| format!("Serial source '{}': {}", source.name, e))?;
+        dme_charset(source.charset.as_deref()).map_err(|e| format!("Serial source '{}': {}", source.name, e))?;
+        let inputs = [source.usb.is_some(), source.network.is_some(), source.pipe.is_some()];
+        if inputs.iter().filter(|input| **input).count() > 1 {
+            return Err(format!("Serial source '{}': only one of 'usb', 'network' and 'pipe' can be set", source.name).into());
+        }
+        if let Some(usb) = &source.usb {
+            usb.ids().map_err(|e| format!("Serial source '{}': {}", source.name, e))?;
+        }
+        if let Some(network) = &source.network {
+            if !network.address.contains(':') {
+                return Err(format!("Serial source '{}': network address '{}' needs host:port", source.name, network.address).into());
+            }
+        }
+        if !inputs.contains(&true) && source.port.trim().is_empty() {
+            return Err(format!("Serial source '{}': 'port', 'usb', 'network' or 'pipe' required", source.name).into());
+        }
+    }
+    Ok(())
+}
+
+fn validate_stichwoerter(general: &GeneralConfig, alarm_templates: &AlarmTemplates) -> Result<(), Box<dyn Error>> {
+    for (keyword, stichwort) in &general.stichwoerter {
+        for template in &stichwort.templates {
+            if !alarm_templates.templates.contains_key(template) {
+                return Err(format!("Stichwort '{}': template '{}' not found", keyword, template).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+fn validate_vehicles(general: &GeneralConfig) -> Result<(), Box<dyn Error>> {
+    let divera = general.apis.iter().any(|api| matches!(api.api, ApiType::Divera));
+    for (id, vehicle) in &general.vehicles {
+        if vehicle.divera_id.is_some() && !divera {
+            return Err(format!("Vehicle '{}' ({}): 'divera_id' needs a Divera API in 'apis'", vehicle.name, id).into());
+        }
+    }
+    Ok(())
+}
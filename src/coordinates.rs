@@ -0,0 +1,389 @@
+use std::sync::LazyLock;
+use regex::Regex;
+use crate::alarm::Coordinates;
+
+/// Ellipsoid and projection parameters of a transverse mercator grid.
+struct TransverseMercator {
+    a: f64,
+    f: f64,
+    k0: f64,
+    lon0: f64,
+    false_easting: f64,
+    false_northing: f64,
+}
+
+const WGS84_A: f64 = 6378137.0;
+const WGS84_F: f64 = 1.0 / 298.257223563;
+const BESSEL_A: f64 = 6377397.155;
+const BESSEL_F: f64 = 1.0 / 299.1528128;
+
+/// MGRS / UTM latitude bands, 8° each starting at 80°S (X is 12°).
+const BANDS: &str = "CDEFGHJKLMNPQRSTUVWX";
+/// MGRS 100 km column letters, one set per `zone % 3`.
+const MGRS_COLUMNS: [&str; 3] = ["ABCDEFGH", "JKLMNPQR", "STUVWXYZ"];
+/// MGRS 100 km row letters, shifted by 5 in even zones.
+const MGRS_ROWS: &str = "ABCDEFGHJKLMNPQRSTUV";
+
+/// grid values in meters, at least 5 digits so "49,1234 / 8,5678" is not taken for UTM
+const GRID_NUMBER: &str = r"(\d{5,}(?:[.,]\d+)?)";
+const SEPARATOR: &str = r"\s*(?:[,;/]\s*|\s+)";
+
+/// compact MGRS reference, upper case without spaces
+static MGRS_COMPACT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d{1,2})([C-HJ-NP-X])([A-HJ-NP-Z])([A-HJ-NP-V])(\d*)$").unwrap());
+static MGRS: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?i)^\d{1,2}\s*[C-HJ-NP-X]\s*[A-HJ-NP-Z][A-HJ-NP-V](?:\s*\d+)*$").unwrap());
+static UTM: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i)^(?:UTM\s*)?(\d{{1,2}})\s*([C-HJ-NP-X])?{}(?:E\s*)?{}\s*m?{}(?:N\s*)?{}\s*m?$",
+        SEPARATOR, GRID_NUMBER, SEPARATOR, GRID_NUMBER
+    ))
+    .unwrap()
+});
+/// UTM with the zone in front of the easting, common in German dispatch systems
+static UTM_PREFIXED: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(&format!(r"^(\d{{2}})(\d{{6}}(?:[.,]\d+)?){}(\d{{7}}(?:[.,]\d+)?)$", SEPARATOR)).unwrap());
+static GAUSS_KRUEGER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"(?i)^(?:R(?:echtswert)?\s*[:=]?\s*)?(\d{{7}}(?:[.,]\d+)?){}(?:H(?:ochwert)?\s*[:=]?\s*)?(\d{{7}}(?:[.,]\d+)?)$",
+        SEPARATOR
+    ))
+    .unwrap()
+});
+static DECIMAL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"-?\d+[.,]\d+").unwrap());
+/// `50 8` / `50, 8`, whole degrees only if there is nothing else in the value
+static WHOLE_DEGREES: LazyLock<Regex> = LazyLock::new(|| Regex::new(&format!(r"^(-?\d{{1,3}}){}(-?\d{{1,3}})$", SEPARATOR)).unwrap());
+
+impl TransverseMercator {
+    /// Grid coordinates to geographic (degrees) on the same ellipsoid.
+    fn inverse(&self, easting: f64, northing: f64) -> (f64, f64) {
+        let e2 = self.f * (2.0 - self.f);
+        let ep2 = e2 / (1.0 - e2);
+        let e1 = (1.0 - (1.0 - e2).sqrt()) / (1.0 + (1.0 - e2).sqrt());
+
+        let x = easting - self.false_easting;
+        let m = (northing - self.false_northing) / self.k0;
+        let mu = m / (self.a * (1.0 - e2 / 4.0 - 3.0 * e2.powi(2) / 64.0 - 5.0 * e2.powi(3) / 256.0));
+
+        let phi1 = mu
+            + (3.0 * e1 / 2.0 - 27.0 * e1.powi(3) / 32.0) * (2.0 * mu).sin()
+            + (21.0 * e1.powi(2) / 16.0 - 55.0 * e1.powi(4) / 32.0) * (4.0 * mu).sin()
+            + (151.0 * e1.powi(3) / 96.0) * (6.0 * mu).sin()
+            + (1097.0 * e1.powi(4) / 512.0) * (8.0 * mu).sin();
+
+        let sin_phi1 = phi1.sin();
+        let cos_phi1 = phi1.cos();
+        let n1 = self.a / (1.0 - e2 * sin_phi1.powi(2)).sqrt();
+        let t1 = phi1.tan().powi(2);
+        let c1 = ep2 * cos_phi1.powi(2);
+        let r1 = self.a * (1.0 - e2) / (1.0 - e2 * sin_phi1.powi(2)).powf(1.5);
+        let d = x / (n1 * self.k0);
+
+        let lat = phi1
+            - (n1 * phi1.tan() / r1)
+                * (d.powi(2) / 2.0
+                    - (5.0 + 3.0 * t1 + 10.0 * c1 - 4.0 * c1.powi(2) - 9.0 * ep2) * d.powi(4) / 24.0
+                    + (61.0 + 90.0 * t1 + 298.0 * c1 + 45.0 * t1.powi(2) - 252.0 * ep2 - 3.0 * c1.powi(2)) * d.powi(6) / 720.0);
+        let lon = (d - (1.0 + 2.0 * t1 + c1) * d.powi(3) / 6.0
+            + (5.0 - 2.0 * c1 + 28.0 * t1 - 3.0 * c1.powi(2) + 8.0 * ep2 + 24.0 * t1.powi(2)) * d.powi(5) / 120.0)
+            / cos_phi1;
+
+        (lat.to_degrees(), self.lon0 + lon.to_degrees())
+    }
+}
+
+/// Checks the ranges of a lat/lon pair.
+pub fn validate(lat: f64, lon: f64) -> Result<Coordinates, String> {
+    if !lat.is_finite() || !(-90.0..=90.0).contains(&lat) {
+        return Err(format!("Latitude {} out of range", lat));
+    }
+    if !lon.is_finite() || !(-180.0..=180.0).contains(&lon) {
+        return Err(format!("Longitude {} out of range", lon));
+    }
+    Ok(Coordinates { lat: Some(lat), lon: Some(lon) })
+}
+
+/// Decimal number with a decimal point or comma, e.g. `-8.5678` or `49,1234`.
+pub fn parse_decimal(value: &str) -> Option<f64> {
+    value.trim().replace(',', ".").parse::<f64>().ok()
+}
+
+/// WGS84 UTM coordinates. `north` selects the hemisphere.
+pub fn from_utm(zone: u32, north: bool, easting: f64, northing: f64) -> Result<Coordinates, String> {
+    if !(1..=60).contains(&zone) {
+        return Err(format!("UTM zone {} out of range", zone));
+    }
+    if !(100_000.0..1_000_000.0).contains(&easting) {
+        return Err(format!("UTM easting {} out of range", easting));
+    }
+    if !(0.0..=10_000_000.0).contains(&northing) {
+        return Err(format!("UTM northing {} out of range", northing));
+    }
+
+    let projection = TransverseMercator {
+        a: WGS84_A,
+        f: WGS84_F,
+        k0: 0.9996,
+        lon0: zone as f64 * 6.0 - 183.0,
+        false_easting: 500_000.0,
+        false_northing: if north { 0.0 } else { 10_000_000.0 },
+    };
+    let (lat, lon) = projection.inverse(easting, northing);
+    validate(lat, lon)
+}
+
+/// MGRS reference like `32UMU 61344 81745` (1 to 5 digits per axis).
+pub fn from_mgrs(value: &str) -> Result<Coordinates, String> {
+    let compact: String = value.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_uppercase();
+    let captures = MGRS_COMPACT.captures(&compact).ok_or_else(|| format!("Not a MGRS reference: '{}'", value))?;
+
+    let zone: u32 = captures[1].parse().map_err(|_| format!("Invalid MGRS zone in '{}'", value))?;
+    if !(1..=60).contains(&zone) {
+        return Err(format!("MGRS zone {} out of range", zone));
+    }
+    let band = captures[2].chars().next().unwrap();
+    let column = captures[3].chars().next().unwrap();
+    let row = captures[4].chars().next().unwrap();
+    let digits = &captures[5];
+    if !digits.len().is_multiple_of(2) || digits.len() > 10 {
+        return Err(format!("Invalid MGRS digits in '{}'", value));
+    }
+
+    let column_index = MGRS_COLUMNS[((zone - 1) % 3) as usize]
+        .find(column)
+        .ok_or_else(|| format!("Invalid MGRS column letter '{}' for zone {}", column, zone))?;
+    let row_offset = if zone.is_multiple_of(2) { 5 } else { 0 };
+    let row_index = (MGRS_ROWS.find(row).unwrap() + 20 - row_offset) % 20;
+
+    let (east_digits, north_digits) = digits.split_at(digits.len() / 2);
+    let scale = 10f64.powi(5 - east_digits.len() as i32);
+    let east = if east_digits.is_empty() { 0.0 } else { east_digits.parse::<f64>().unwrap() * scale };
+    let north = if north_digits.is_empty() { 0.0 } else { north_digits.parse::<f64>().unwrap() * scale };
+
+    let easting = (column_index as f64 + 1.0) * 100_000.0 + east;
+    let north_in_cycle = row_index as f64 * 100_000.0 + north;
+
+    // the row letters repeat every 2000 km, the latitude band decides which cycle is meant
+    let band_index = BANDS.find(band).unwrap();
+    let band_min = -80.0 + 8.0 * band_index as f64;
+    let band_max = if band == 'X' { 84.0 } else { band_min + 8.0 };
+    let hemisphere_north = band >= 'N';
+
+    (0..5)
+        .map(|cycle| north_in_cycle + cycle as f64 * 2_000_000.0)
+        .filter_map(|northing| from_utm(zone, hemisphere_north, easting, northing).ok())
+        .find(|coords| {
+            let lat = coords.lat.unwrap_or_default();
+            lat >= band_min - 0.5 && lat <= band_max + 0.5
+        })
+        .ok_or_else(|| format!("MGRS reference '{}' is outside of band {}", value, band))
+}
+
+/// Gauss-Krüger (DHDN / Potsdam datum, 3° zones) to WGS84. The zone is the
+/// first digit of the 7 digit Rechtswert.
+pub fn from_gauss_krueger(rechtswert: f64, hochwert: f64) -> Result<Coordinates, String> {
+    let zone = (rechtswert / 1_000_000.0).floor();
+    if !(1.0..=9.0).contains(&zone) {
+        return Err(format!("Gauss-Krüger Rechtswert {} out of range", rechtswert));
+    }
+    if !(1_000_000.0..10_000_000.0).contains(&hochwert) {
+        return Err(format!("Gauss-Krüger Hochwert {} out of range", hochwert));
+    }
+
+    let projection = TransverseMercator {
+        a: BESSEL_A,
+        f: BESSEL_F,
+        k0: 1.0,
+        lon0: zone * 3.0,
+        false_easting: zone * 1_000_000.0 + 500_000.0,
+        false_northing: 0.0,
+    };
+    let (lat, lon) = projection.inverse(rechtswert, hochwert);
+    let (lat, lon) = dhdn_to_wgs84(lat, lon);
+    validate(lat, lon)
+}
+
+fn to_cartesian(lat: f64, lon: f64, a: f64, f: f64) -> (f64, f64, f64) {
+    let e2 = f * (2.0 - f);
+    let (lat, lon) = (lat.to_radians(), lon.to_radians());
+    let n = a / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+    (n * lat.cos() * lon.cos(), n * lat.cos() * lon.sin(), n * (1.0 - e2) * lat.sin())
+}
+
+fn from_cartesian(x: f64, y: f64, z: f64, a: f64, f: f64) -> (f64, f64) {
+    let e2 = f * (2.0 - f);
+    let p = (x * x + y * y).sqrt();
+    let mut lat = (z / (p * (1.0 - e2))).atan();
+    for _ in 0..10 {
+        let n = a / (1.0 - e2 * lat.sin().powi(2)).sqrt();
+        lat = ((z + e2 * n * lat.sin()) / p).atan();
+    }
+    (lat.to_degrees(), y.atan2(x).to_degrees())
+}
+
+/// Datum shift DHDN (Bessel) -> WGS84, 7 parameter Helmert transformation
+/// with the nationwide parameters for Germany (accurate to a few meters).
+fn dhdn_to_wgs84(lat: f64, lon: f64) -> (f64, f64) {
+    let (x, y, z) = to_cartesian(lat, lon, BESSEL_A, BESSEL_F);
+
+    let arcsec = |value: f64| (value / 3600.0).to_radians();
+    let (tx, ty, tz) = (598.1, 73.7, 418.2);
+    let (rx, ry, rz) = (arcsec(0.202), arcsec(0.045), arcsec(-2.455));
+    let s = 1.0 + 6.7e-6;
+
+    let x2 = tx + s * (x - rz * y + ry * z);
+    let y2 = ty + s * (rz * x + y - rx * z);
+    let z2 = tz + s * (-ry * x + rx * y + z);
+
+    from_cartesian(x2, y2, z2, WGS84_A, WGS84_F)
+}
+
+/// Parses coordinates in any supported notation:
+///
+/// - lat/lon in decimal degrees: `49.1234, 8.5678`, `49,1234 / -8,5678`
+/// - UTM: `32U 461344 5481745`, `32 U E 461344 N 5481745`, `32461344 5481745`
+/// - MGRS: `32UMU 61344 81745`, `32UMU6134481745`
+/// - Gauss-Krüger: `R 3461344 H 5481745`, `3461344 5481745`
+pub fn parse(value: &str) -> Result<Coordinates, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Err("No coordinates".to_string());
+    }
+
+    // MGRS, before UTM because both start with zone and band
+    if MGRS.is_match(value) {
+        return from_mgrs(value);
+    }
+
+    if let Some(captures) = UTM.captures(value) {
+        let zone: u32 = captures[1].parse().map_err(|_| format!("Invalid UTM zone in '{}'", value))?;
+        let north = match captures.get(2) {
+            Some(band) => {
+                let band = band.as_str().to_ascii_uppercase();
+                band.as_str() >= "N"
+            }
+            None => true,
+        };
+        let easting = parse_decimal(&captures[3]).ok_or_else(|| format!("Invalid UTM easting in '{}'", value))?;
+        let northing = parse_decimal(&captures[4]).ok_or_else(|| format!("Invalid UTM northing in '{}'", value))?;
+        return from_utm(zone, north, easting, northing);
+    }
+
+    if let Some(captures) = UTM_PREFIXED.captures(value) {
+        let zone: u32 = captures[1].parse().unwrap();
+        let easting = parse_decimal(&captures[2]).unwrap();
+        let northing = parse_decimal(&captures[3]).unwrap();
+        return from_utm(zone, true, easting, northing);
+    }
+
+    if let Some(captures) = GAUSS_KRUEGER.captures(value) {
+        let rechtswert = parse_decimal(&captures[1]).unwrap();
+        let hochwert = parse_decimal(&captures[2]).unwrap();
+        return from_gauss_krueger(rechtswert, hochwert);
+    }
+
+    parse_lat_lon(value)
+}
+
+/// `lat, lon` in decimal degrees: the first two decimal numbers, or a value
+/// of just two whole degrees. Negative values and both decimal points and
+/// decimal commas are accepted, out of range values are an error.
+pub fn parse_lat_lon(value: &str) -> Result<Coordinates, String> {
+    let decimals: Vec<f64> = DECIMAL.find_iter(value).filter_map(|m| parse_decimal(m.as_str())).collect();
+    if let [lat, lon, ..] = decimals.as_slice() {
+        return validate(*lat, *lon);
+    }
+
+    match WHOLE_DEGREES.captures(value.trim()) {
+        Some(captures) => validate(parse_decimal(&captures[1]).unwrap(), parse_decimal(&captures[2]).unwrap()),
+        None => Err(format!("Unknown coordinate format: '{}'", value)),
+    }
+}
+
+/// Great circle distance in meters (haversine).
+pub fn distance_m(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    const EARTH_RADIUS_M: f64 = 6_371_000.0;
+    let d_lat = (lat2 - lat1).to_radians();
+    let d_lon = (lon2 - lon1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.to_radians().cos() * lat2.to_radians().cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_M * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reference points in WGS84 with their grid values from an independent
+    /// forward transverse mercator (Krüger series) and the same Helmert parameters.
+    const ROEMER: (f64, f64) = (50.110556, 8.682222);
+    const BRANDENBURGER_TOR: (f64, f64) = (52.516275, 13.377704);
+    const WASHINGTON_MONUMENT: (f64, f64) = (38.889484, -77.035278);
+    const OPERA_SYDNEY: (f64, f64) = (-33.856784, 151.215297);
+
+    fn assert_near(value: &str, expected: (f64, f64), tolerance_m: f64) {
+        let coords = parse(value).unwrap_or_else(|e| panic!("{}: {}", value, e));
+        let distance = distance_m(coords.lat.unwrap(), coords.lon.unwrap(), expected.0, expected.1);
+        assert!(distance < tolerance_m, "{}: {:.2} m off ({:?})", value, distance, coords);
+    }
+
+    #[test]
+    fn utm_reference_points() {
+        assert_near("32U 477278.07 5550971.29", ROEMER, 0.5);
+        assert_near("32 U E 477278 N 5550971", ROEMER, 1.0);
+        assert_near("32477278 5550971", ROEMER, 1.0);
+        assert_near("UTM 33U 389918 5819699", BRANDENBURGER_TOR, 1.0);
+        assert_near("18S 323480 4306481", WASHINGTON_MONUMENT, 1.0);
+        assert_near("56H 334900 6252291", OPERA_SYDNEY, 1.0);
+    }
+
+    #[test]
+    fn mgrs_reference_points() {
+        // MGRS truncates to the metre, so the point may be up to 1.4 m away
+        assert_near("32UMA 77278 50971", ROEMER, 1.5);
+        assert_near("32UMA7727850971", ROEMER, 1.5);
+        assert_near("18SUJ 23479 06481", WASHINGTON_MONUMENT, 1.5);
+        assert_near("56HLH 34900 52290", OPERA_SYDNEY, 1.5);
+        assert_near("32UMA 772 509", ROEMER, 150.0);
+    }
+
+    #[test]
+    fn gauss_krueger_reference_points() {
+        assert_near("R 3477343.38 H 5552752.45", ROEMER, 1.0);
+        assert_near("3477343 5552752", ROEMER, 1.5);
+        assert_near("Rechtswert: 4593627 Hochwert: 5821242", BRANDENBURGER_TOR, 1.5);
+        assert_near("5390009 5821582", BRANDENBURGER_TOR, 1.5);
+    }
+
+    #[test]
+    fn lat_lon() {
+        assert_near("50.110556, 8.682222", ROEMER, 0.1);
+        assert_near("50,110556 / 8,682222", ROEMER, 0.1);
+        assert_near("-33.856784 151.215297", OPERA_SYDNEY, 0.1);
+        assert_near("50, 9", (50.0, 9.0), 0.1);
+        assert_near("-34 151", (-34.0, 151.0), 0.1);
+    }
+
+    #[test]
+    fn malformed_input() {
+        for value in [
+            "",
+            "   ",
+            "Hauptstraße 5",
+            "95.1, 8.2",
+            "50.1, 181.0",
+            "61U 477278 5550971",
+            "32U 47727 5550971",
+            "32UAA 77278 50971",
+            "32UMA 7727 5097 1",
+            "32UMA 123456123456",
+            "R 0477343 H 5552752",
+            "3477343 0552752",
+            "95 8",
+            "50 181",
+            "Hauptstraße 12, 34",
+            "12, 34 Musterstadt",
+            "1234 5678",
+        ] {
+            assert!(parse(value).is_err(), "'{}' should not parse", value);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use crate::alarm::DmeData;
+use crate::dme_decoder::{normalize_date, strip_status_bytes, sub_address, DmeDecoder};
+
+/// Hörmann/Funkwerk DME: one line with `;` (or tab) separated fields, the
+/// timestamp (one or two fields), RIC, sub-address (`A`-`D` or `1`-`4`) and text.
+/// `19.10.2026;14:22:05;1234567;3;B3 Wohnhaus`
+pub struct FunkwerkDecoder;
+
+impl DmeDecoder for FunkwerkDecoder {
+    fn decode(&self, frame: &str) -> Result<DmeData, String> {
+        let frame = strip_status_bytes(frame);
+        let line = frame.lines().map(str::trim).find(|line| !line.is_empty()).ok_or("Empty frame")?;
+        let separator = if line.contains(';') { ';' } else { '\t' };
+        let fields: Vec<&str> = line.split(separator).map(str::trim).collect();
+
+        let ric_idx = fields
+            .iter()
+            .position(|field| (5..=7).contains(&field.len()) && field.chars().all(|c| c.is_ascii_digit()))
+            .ok_or("No RIC field")?;
+
+        let mut text_idx = ric_idx + 1;
+        let sub = fields.get(text_idx).map(|field| sub_address(field)).unwrap_or_default();
+        if !sub.is_empty() {
+            text_idx += 1;
+        }
+
+        Ok(DmeData {
+            kind: String::new(),
+            status: String::new(),
+            date: normalize_date(&fields[..ric_idx].join(" ")),
+            ric: fields[ric_idx].to_string(),
+            sub_address: sub,
+            content: fields.get(text_idx..).map(|text| text.join(&separator.to_string())).unwrap_or_default().trim().to_string(),
+            rics: vec![],
+        })
+    }
+}
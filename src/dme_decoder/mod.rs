@@ -0,0 +1,236 @@
+pub mod standard_decoder;
+pub mod swissphone_decoder;
+pub mod funkwerk_decoder;
+pub mod regex_decoder;
+pub mod multimon_decoder;
+
+use std::sync::LazyLock;
+use chrono::NaiveDateTime;
+use encoding_rs::{Encoding, ISO_8859_2};
+use regex::Regex;
+use crate::alarm::DmeData;
+use crate::config::alarm_sources::SerialConfig;
+use crate::dme_decoder::funkwerk_decoder::FunkwerkDecoder;
+use crate::dme_decoder::multimon_decoder::MultimonDecoder;
+use crate::dme_decoder::regex_decoder::RegexDecoder;
+use crate::dme_decoder::standard_decoder::StandardDecoder;
+use crate::dme_decoder::swissphone_decoder::SwissphoneDecoder;
+
+pub trait DmeDecoder: Send + Sync {
+    /// Decodes one frame, already converted with the source charset and
+    /// without the delimiter. `Err` means the frame is no alarm.
+    fn decode(&self, frame: &str) -> Result<DmeData, String>;
+}
+
+/// All values accepted as `protocol`.
+pub const DME_PROTOCOLS: &[&str] = &["Standard", "Swissphone", "Funkwerk", "Regex", "Multimon"];
+
+/// Decoder registry, unknown protocol names are a config error.
+pub fn decoder_for(config: &SerialConfig) -> Result<Box<dyn DmeDecoder>, String> {
+    match config.protocol.as_str() {
+        "Standard" => Ok(Box::new(StandardDecoder)),
+        "Swissphone" => Ok(Box::new(SwissphoneDecoder)),
+        "Funkwerk" => Ok(Box::new(FunkwerkDecoder)),
+        "Regex" => {
+            let pattern = config.frame_pattern.as_deref().ok_or("protocol 'Regex' requires 'frame_pattern'")?;
+            Ok(Box::new(RegexDecoder::new(pattern)?))
+        }
+        "Multimon" => Ok(Box::new(MultimonDecoder)),
+        _ => Err(format!(
+            "Unknown protocol '{}', available: {}",
+            config.protocol,
+            DME_PROTOCOLS.join(", ")
+        )),
+    }
+}
+
+/// Charset of the receiver, `SerialConfig::charset` or ISO-8859-2.
+pub fn dme_charset(label: Option<&str>) -> Result<&'static Encoding, String> {
+    match label {
+        Some(label) => Encoding::for_label(label.trim().as_bytes()).ok_or_else(|| format!("Unknown charset '{}'", label)),
+        None => Ok(ISO_8859_2),
+    }
+}
+
+/// Removes status and control bytes (STX, ETX, BEL, ...), line breaks and tabs are kept.
+pub fn strip_status_bytes(frame: &str) -> String {
+    frame
+        .chars()
+        .filter(|c| !c.is_control() || matches!(c, '\n' | '\r' | '\t'))
+        .collect()
+}
+
+/// `1234567C` or `1234567-3` -> (`1234567`, `C`). Sub-addresses 1-4 need a
+/// separator, otherwise the digit is part of the RIC.
+pub fn split_ric(value: &str) -> (String, String) {
+    static RIC: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\d+)(?:\s*([A-Da-d])|\s*[-/ ]\s*([1-4]))?$").unwrap());
+    let value = value.trim();
+    match RIC.captures(value) {
+        Some(captures) => (
+            captures[1].to_string(),
+            captures.get(2).or(captures.get(3)).map(|m| sub_address(m.as_str())).unwrap_or_default(),
+        ),
+        None => (value.to_string(), String::new()),
+    }
+}
+
+/// `A`-`D` or `1`-`4` as upper case letter, anything else is empty.
+pub fn sub_address(value: &str) -> String {
+    match value.trim().to_uppercase().as_str() {
+        "A" | "1" => "A".to_string(),
+        "B" | "2" => "B".to_string(),
+        "C" | "3" => "C".to_string(),
+        "D" | "4" => "D".to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Timestamps of the receivers as `DD.MM.YYYY HH:MM:SS`, unknown formats are kept.
+pub fn normalize_date(value: &str) -> String {
+    // two digit years first, `%Y` would read `24` as year 24
+    const FORMATS: &[&str] = &[
+        "%d.%m.%y %H:%M:%S",
+        "%d.%m.%Y %H:%M:%S",
+        "%d.%m.%y %H:%M",
+        "%d.%m.%Y %H:%M",
+        "%H:%M:%S %d.%m.%y",
+        "%H:%M:%S %d.%m.%Y",
+        "%H:%M %d.%m.%y",
+        "%H:%M %d.%m.%Y",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+    ];
+    let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    FORMATS
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(&value, format).ok())
+        .map(|date| date.format("%d.%m.%Y %H:%M:%S").to_string())
+        .unwrap_or(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::{Duration, Instant};
+    use super::*;
+    use crate::frame_reader::FrameReader;
+
+    /// Splits a dump of `fixtures/dme` at `delimiter` and decodes every frame
+    /// with the charset and protocol of `config`, like a serial source.
+    fn decode_dump(file: &str, delimiter: &[u8], config: serde_json::Value) -> Vec<Result<DmeData, String>> {
+        let mut source = serde_json::json!({
+            "name": "Pager",
+            "active": true,
+            "delimiter": "",
+            "alarm_list": [],
+            "rics": {},
+        });
+        for (key, value) in config.as_object().unwrap() {
+            source[key] = value.clone();
+        }
+        let source: SerialConfig = serde_json::from_value(source).unwrap();
+        let decoder = decoder_for(&source).unwrap();
+        let charset = dme_charset(source.charset.as_deref()).unwrap();
+
+        let raw = fs::read(format!("{}/fixtures/dme/{}", env!("CARGO_MANIFEST_DIR"), file)).unwrap();
+        let mut reader = FrameReader::new(delimiter.to_vec(), Duration::ZERO, 0);
+        let mut frames = reader.push(&raw, Instant::now());
+        frames.extend(reader.finish());
+        frames.iter().map(|frame| decoder.decode(&charset.decode(&frame.data).0)).collect()
+    }
+
+    /// date, RIC, sub-address and text
+    fn fields(data: &Result<DmeData, String>) -> (&str, &str, &str, &str) {
+        let data = data.as_ref().unwrap();
+        (&data.date, &data.ric, &data.sub_address, &data.content)
+    }
+
+    #[test]
+    fn standard_dump() {
+        let frames = decode_dump("standard.dme", b"\r\n\r\n", serde_json::json!({}));
+        assert_eq!(frames.len(), 3);
+        assert_eq!(fields(&frames[0]), ("19.10.2026 14:22:00", "1234567", "C", "B3 Wohnhaus Müller"));
+        assert_eq!(fields(&frames[1]), ("19.10.2026 14:30:05", "7654321", "D", "THL 1 Ölspur"));
+        assert_eq!(fields(&frames[2]), ("19.10.2026 14:31:00", "1111111", "", "Probealarm"));
+    }
+
+    #[test]
+    fn swissphone_dump() {
+        let frames = decode_dump("swissphone.dme", b"\r\n\r\n", serde_json::json!({ "protocol": "Swissphone" }));
+        assert_eq!(frames.len(), 3);
+        assert_eq!(fields(&frames[0]), ("19.10.2026 14:22:05", "1234567", "B", "B3 Brand\nHauptstr. 1 München"));
+        assert_eq!(fields(&frames[1]), ("19.10.2026 14:30:00", "7654321", "", "THL 1"));
+        // STX, status byte and BEL before the date, lower case sub-address
+        assert_eq!(fields(&frames[2]), ("19.10.2026 14:31:00", "2222222", "D", "Gefahrgut"));
+    }
+
+    #[test]
+    fn funkwerk_dump() {
+        let frames = decode_dump("funkwerk.dme", b"\n", serde_json::json!({ "protocol": "Funkwerk" }));
+        assert_eq!(frames.len(), 3);
+        assert_eq!(fields(&frames[0]), ("19.10.2026 14:22:05", "1234567", "C", "B3 Wohnhaus;2. OG"));
+        assert_eq!(fields(&frames[1]), ("19.10.2026 14:23:00", "1234567", "", "Probealarm"));
+        // tab separated, sub-address as digit
+        assert_eq!(fields(&frames[2]), ("19.10.2026 14:24:00", "7654321", "A", "THL Straße"));
+    }
+
+    #[test]
+    fn regex_dump() {
+        let config = serde_json::json!({
+            "protocol": "Regex",
+            "charset": "windows-1252",
+            "frame_pattern": r"^RIC (?P<ric>\d+)(?P<sub>[A-D])? (?:(?P<date>\d\S+ \S+) )?(?P<text>.*)$",
+        });
+        let frames = decode_dump("regex.dme", b"\r\n", config);
+        assert_eq!(frames.len(), 2);
+        assert_eq!(fields(&frames[0]), ("19.10.2026 14:22:05", "1234567", "A", "Gefahrgut Straße € 5"));
+        assert_eq!(fields(&frames[1]), ("", "7654321", "", "??? THL 1"));
+    }
+
+    #[test]
+    fn charset_of_the_receiver() {
+        // 0xB9 is `š` in ISO-8859-2 (the default) and `¹` in windows-1252
+        let frame = b"19.10.26 14:22\r\n1234567\r\n\xb9 \xdf";
+        let iso = dme_charset(None).unwrap().decode(frame).0;
+        let windows = dme_charset(Some("windows-1252")).unwrap().decode(frame).0;
+        assert_eq!(StandardDecoder.decode(&iso).unwrap().content, "š ß");
+        assert_eq!(StandardDecoder.decode(&windows).unwrap().content, "¹ ß");
+        assert!(dme_charset(Some("klingon")).is_err());
+    }
+
+    #[test]
+    fn status_bytes_are_removed() {
+        assert_eq!(strip_status_bytes("\u{2}\u{1}B3\u{3}\r\nTHL\t1\u{7}"), "B3\r\nTHL\t1");
+    }
+
+    #[test]
+    fn sub_addresses() {
+        assert_eq!(split_ric("1234567A"), ("1234567".to_string(), "A".to_string()));
+        assert_eq!(split_ric("1234567 b"), ("1234567".to_string(), "B".to_string()));
+        assert_eq!(split_ric("1234567-3"), ("1234567".to_string(), "C".to_string()));
+        assert_eq!(split_ric("1234567/4"), ("1234567".to_string(), "D".to_string()));
+        // without separator the digit belongs to the RIC
+        assert_eq!(split_ric("12345674"), ("12345674".to_string(), String::new()));
+        assert_eq!(split_ric("1234567-5"), ("1234567-5".to_string(), String::new()));
+        assert_eq!(sub_address("E"), "");
+    }
+
+    #[test]
+    fn date_formats() {
+        assert_eq!(normalize_date("19.10.26 14:22"), "19.10.2026 14:22:00");
+        assert_eq!(normalize_date("19.10.2026  14:22:05"), "19.10.2026 14:22:05");
+        assert_eq!(normalize_date("14:22:05 19.10.26"), "19.10.2026 14:22:05");
+        assert_eq!(normalize_date("14:22 19.10.2026"), "19.10.2026 14:22:00");
+        assert_eq!(normalize_date("2026-10-19T14:22:05"), "19.10.2026 14:22:05");
+        assert_eq!(normalize_date("Mo 14 Uhr"), "Mo 14 Uhr");
+    }
+
+    #[test]
+    fn unknown_protocol() {
+        let source: SerialConfig = serde_json::from_value(serde_json::json!({
+            "name": "Pager", "active": true, "delimiter": "", "alarm_list": [], "rics": {}, "protocol": "Telefax",
+        }))
+        .unwrap();
+        assert!(decoder_for(&source).is_err());
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::LazyLock;
+use chrono::Local;
+use regex::Regex;
+use crate::alarm::DmeData;
+use crate::dme_decoder::{normalize_date, strip_status_bytes, sub_address, DmeDecoder};
+
+static LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\s*(?:(?P<date>\d{4}-\d{2}-\d{2}[ T]\d{2}:\d{2}:\d{2}):\s*)?(?P<kind>[A-Z]+\d*):\s*(?P<line>.*)$").unwrap()
+});
+static POCSAG: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^Address:\s*(?P<ric>\d+)\s+Function:\s*(?P<function>[0-3])\s*(?:(?:Alpha|Numeric):\s*(?P<text>.*))?$").unwrap()
+});
+/// multimon-ng prints control characters as `<NUL>`, `<EOT>`, ...
+static CONTROL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<(?:NUL|SOH|STX|ETX|EOT|ENQ|ACK|BEL|BS|HT|LF|VT|FF|CR|SO|SI|DLE|DC[1-4]|NAK|SYN|ETB|CAN|EM|SUB|ESC|FS|GS|RS|US|DEL)>").unwrap()
+});
+static FMS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"Ort\s+0x[0-9a-fA-F]+=(?P<ort>\d+)\s*FZG\s+(?P<fzg>\d+)\s+Status\s+(?P<status>[0-9a-fA-F])=(?P<text>.*?)\s+[01]=(?P<direction>\S+)").unwrap()
+});
+
+/// Output lines of multimon-ng (`-a POCSAG1200 -a ZVEI1 -a FMSFSK`), optionally
+/// with the `--timestamp` prefix:
+///
+/// - `POCSAG1200: Address: 1234567  Function: 2  Alpha:   text` -> RIC `1234567` (padded to seven digits), sub-address `C`
+/// - `ZVEI1: 25E34` -> RIC `25534`, `E` repeats the previous digit
+/// - `FMS: ... Ort 0x25=037FZG 1721 Status 3=Einsatz Ab ... CRC correct` -> RIC `0371721`, text `Status 3 Einsatz Ab`, status `3`
+pub struct MultimonDecoder;
+
+impl MultimonDecoder {
+    fn pocsag(kind: &str, line: &str) -> Result<DmeData, String> {
+        let captures = POCSAG.captures(line).ok_or("Invalid POCSAG line")?;
+        let text = captures.name("text").map(|m| CONTROL.replace_all(m.as_str(), " ").trim().to_string()).unwrap_or_default();
+
+        Ok(DmeData {
+            kind: kind.to_string(),
+            status: String::new(),
+            date: String::new(),
+            // seven digits like the DME receivers, so the same `rics` keys apply
+            ric: format!("{:0>7}", &captures["ric"]),
+            sub_address: sub_address(&(captures["function"].parse::<u8>().unwrap() + 1).to_string()),
+            content: text,
+            rics: vec![],
+        })
+    }
+
+    fn zvei(kind: &str, line: &str) -> Result<DmeData, String> {
+        let mut code = String::new();
+        for c in line.trim().chars() {
+            match c {
+                '0'..='9' => code.push(c),
+                'E' => code.push(code.chars().last().ok_or("ZVEI code starts with repeat tone")?),
+                _ => return Err(format!("Invalid ZVEI code '{}'", line.trim())),
+            }
+        }
+        if code.len() != 5 {
+            return Err(format!("ZVEI code '{}' has not 5 digits", line.trim()));
+        }
+
+        Ok(DmeData {
+            kind: kind.to_string(),
+            status: String::new(),
+            date: String::new(),
+            ric: code,
+            sub_address: String::new(),
+            content: String::new(),
+            rics: vec![],
+        })
+    }
+
+    fn fms(line: &str) -> Result<DmeData, String> {
+        if !line.contains("CRC correct") {
+            return Err("FMS telegram without correct CRC".to_string());
+        }
+        let captures = FMS.captures(line).ok_or("Invalid FMS telegram")?;
+
+        // telegrams of the Leitstelle to the vehicle (`1=LST->FZG`) are no vehicle status
+        let from_vehicle = captures["direction"].starts_with("FZG");
+
+        Ok(DmeData {
+            kind: "FMS".to_string(),
+            status: if from_vehicle { captures["status"].to_uppercase() } else { String::new() },
+            date: String::new(),
+            ric: format!("{}{}", &captures["ort"], &captures["fzg"]),
+            sub_address: String::new(),
+            content: format!("Status {} {}", captures["status"].to_uppercase(), captures["text"].trim()).trim().to_string(),
+            rics: vec![],
+        })
+    }
+}
+
+impl DmeDecoder for MultimonDecoder {
+    fn decode(&self, frame: &str) -> Result<DmeData, String> {
+        let frame = strip_status_bytes(frame);
+        let captures = LINE.captures(frame.trim_end()).ok_or("No multimon-ng line")?;
+        let kind = &captures["kind"];
+        let line = &captures["line"];
+
+        let mut data = if kind.starts_with("POCSAG") {
+            Self::pocsag(kind, line)?
+        } else if kind.trim_start_matches(['D', 'P']).starts_with("ZVEI") {
+            Self::zvei(kind, line)?
+        } else if kind == "FMS" {
+            Self::fms(line)?
+        } else {
+            return Err(format!("Unsupported multimon-ng mode '{}'", kind));
+        };
+        data.date = match captures.name("date") {
+            Some(date) => normalize_date(date.as_str()),
+            None => Local::now().format("%d.%m.%Y %H:%M:%S").to_string(),
+        };
+        Ok(data)
+    }
+}
@@ -0,0 +1,40 @@
+use regex::Regex;
+use crate::alarm::DmeData;
+use crate::dme_decoder::{normalize_date, split_ric, strip_status_bytes, sub_address, DmeDecoder};
+
+/// `frame_pattern` with the named groups `ric` (required), `sub`, `date` and `text`,
+/// or `status` for FMS telegrams (`ric` is the FMS id then).
+pub struct RegexDecoder {
+    regex: Regex,
+}
+
+impl RegexDecoder {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let regex = Regex::new(pattern).map_err(|e| format!("Invalid frame_pattern '{}': {}", pattern, e))?;
+        if !regex.capture_names().any(|name| name == Some("ric")) {
+            return Err(format!("frame_pattern '{}' has no 'ric' group", pattern));
+        }
+        Ok(Self { regex })
+    }
+}
+
+impl DmeDecoder for RegexDecoder {
+    fn decode(&self, frame: &str) -> Result<DmeData, String> {
+        let frame = strip_status_bytes(frame);
+        let captures = self.regex.captures(&frame).ok_or("frame_pattern does not match")?;
+        let group = |name: &str| captures.name(name).map(|m| m.as_str().trim().to_string()).unwrap_or_default();
+
+        let (ric, ric_sub_address) = split_ric(&group("ric"));
+        let sub = sub_address(&group("sub"));
+        let status = group("status");
+        Ok(DmeData {
+            kind: if status.is_empty() { String::new() } else { "FMS".to_string() },
+            status,
+            date: normalize_date(&group("date")),
+            ric,
+            sub_address: if sub.is_empty() { ric_sub_address } else { sub },
+            content: group("text"),
+            rics: vec![],
+        })
+    }
+}
@@ -0,0 +1,25 @@
+use crate::alarm::DmeData;
+use crate::dme_decoder::{normalize_date, split_ric, DmeDecoder};
+
+/// Line 0 date, line 1 RIC (optionally with sub-address), line 2 text.
+pub struct StandardDecoder;
+
+impl DmeDecoder for StandardDecoder {
+    fn decode(&self, frame: &str) -> Result<DmeData, String> {
+        let lines: Vec<&str> = frame.trim().lines().collect();
+        if lines.len() < 3 {
+            return Err("Received data is too short".to_string());
+        }
+
+        let (ric, sub_address) = split_ric(lines[1]);
+        Ok(DmeData {
+            kind: String::new(),
+            status: String::new(),
+            date: normalize_date(lines[0]),
+            ric,
+            sub_address,
+            content: lines[2].to_string(),
+            rics: vec![],
+        })
+    }
+}
@@ -0,0 +1,33 @@
+use std::sync::LazyLock;
+use regex::Regex;
+use crate::alarm::DmeData;
+use crate::dme_decoder::{normalize_date, strip_status_bytes, sub_address, DmeDecoder};
+
+static HEADER: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?s)^\s*(?P<date>\d{1,2}\.\d{1,2}\.\d{2,4}\s+\d{1,2}:\d{2}(?::\d{2})?|\d{1,2}:\d{2}(?::\d{2})?\s+\d{1,2}\.\d{1,2}\.\d{2,4})\s+(?P<ric>\d{5,7})\s*(?P<sub>[A-Da-d])?(?:\s+|$)(?P<text>.*)$",
+    )
+    .unwrap()
+});
+
+/// Swissphone DME: leading status bytes, then date and time (either order),
+/// the RIC with sub-address letter (`1234567C`) and the text, on one line
+/// or on separate lines.
+pub struct SwissphoneDecoder;
+
+impl DmeDecoder for SwissphoneDecoder {
+    fn decode(&self, frame: &str) -> Result<DmeData, String> {
+        let frame = strip_status_bytes(frame);
+        let captures = HEADER.captures(&frame).ok_or("No Swissphone header (date, time, RIC)")?;
+
+        Ok(DmeData {
+            kind: String::new(),
+            status: String::new(),
+            date: normalize_date(&captures["date"]),
+            ric: captures["ric"].to_string(),
+            sub_address: captures.name("sub").map(|m| sub_address(m.as_str())).unwrap_or_default(),
+            content: captures["text"].lines().map(str::trim).filter(|line| !line.is_empty()).collect::<Vec<_>>().join("\n"),
+            rics: vec![],
+        })
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use log::{debug, info, warn};
+use serde_json::Value;
+use crate::alarm::{Alarm, Attachment, Hydrant, ObjectInfo};
+use crate::config::general::EnrichmentConfig;
+use crate::coordinates::distance_m;
+use crate::geocoder::normalize;
+
+/// Adds nearby hydrants and the matching object database entry to alarms.
+pub struct Enrichment {
+    hydrants: Vec<Hydrant>,
+    hydrant_radius_m: f64,
+    max_hydrants: usize,
+    objects: Vec<ObjectInfo>,
+    /// Local Einsatzplan/Laufkarte files by path, read at startup
+    documents: HashMap<String, Attachment>,
+}
+
+impl Enrichment {
+    pub fn new(config: &EnrichmentConfig) -> Result<Self, String> {
+        let hydrants = match &config.hydrants {
+            Some(path) => {
+                let content = fs::read_to_string(path).map_err(|e| format!("Could not read hydrants {}: {}", path, e))?;
+                let hydrants = if path.to_lowercase().ends_with(".csv") {
+                    parse_hydrants_csv(&content)
+                } else {
+                    parse_hydrants_geojson(&content)
+                }
+                .map_err(|e| format!("Hydrants {}: {}", path, e))?;
+                info!("Hydranten geladen aus {}: {}", path, hydrants.len());
+                hydrants
+            }
+            None => vec![],
+        };
+
+        let objects = match &config.objects {
+            Some(path) => {
+                let content = fs::read_to_string(path).map_err(|e| format!("Could not read objects {}: {}", path, e))?;
+                let objects: Vec<ObjectInfo> = serde_json::from_str(&content).map_err(|e| format!("Objects {}: {}", path, e))?;
+                info!("Objekte geladen aus {}: {}", path, objects.len());
+                objects
+            }
+            None => vec![],
+        };
+
+        let mut documents = HashMap::new();
+        for object in &objects {
+            for document in [&object.einsatzplan, &object.laufkarte] {
+                let path = document.trim();
+                if documents.contains_key(path) {
+                    continue;
+                }
+                if let Some(attachment) = load_document(path) {
+                    documents.insert(path.to_string(), attachment);
+                }
+            }
+        }
+        if !documents.is_empty() {
+            info!("Objektdokumente geladen: {}", documents.len());
+        }
+
+        Ok(Self {
+            hydrants,
+            hydrant_radius_m: config.hydrant_radius_m,
+            max_hydrants: config.max_hydrants,
+            objects,
+            documents,
+        })
+    }
+
+    pub fn enrich(&self, alarm: &mut Alarm) {
+        if let (Some(lat), Some(lon)) = (alarm.address.coords.lat, alarm.address.coords.lon) {
+            alarm.hydrants = self.nearest_hydrants(lat, lon);
+            debug!("{} Hydranten im Umkreis von {} m", alarm.hydrants.len(), self.hydrant_radius_m);
+        }
+
+        if let Some(object) = self.find_object(alarm) {
+            info!("Objekt gefunden: {} ({})", object.name, object.id);
+            if alarm.address.object.trim().is_empty() {
+                alarm.address.set_object(object.name.clone());
+            }
+            for document in [&object.einsatzplan, &object.laufkarte] {
+                if let Some(attachment) = self.documents.get(document.trim()) {
+                    alarm.add_attachment(attachment.clone());
+                }
+            }
+            alarm.object_info = Some(object.clone());
+        }
+    }
+
+    fn nearest_hydrants(&self, lat: f64, lon: f64) -> Vec<Hydrant> {
+        let mut hydrants: Vec<Hydrant> = self
+            .hydrants
+            .iter()
+            .map(|hydrant| Hydrant { distance_m: distance_m(lat, lon, hydrant.lat, hydrant.lon), ..hydrant.clone() })
+            .filter(|hydrant| hydrant.distance_m <= self.hydrant_radius_m)
+            .collect();
+        hydrants.sort_by(|a, b| a.distance_m.total_cmp(&b.distance_m));
+        hydrants.truncate(self.max_hydrants);
+        hydrants
+    }
+
+    /// By `object_id` first, otherwise by street and house number (and city if both have one).
+    fn find_object(&self, alarm: &Alarm) -> Option<&ObjectInfo> {
+        let object_id = alarm.address.object_id.trim();
+        if !object_id.is_empty() {
+            if let Some(object) = self.objects.iter().find(|object| object.id.trim().eq_ignore_ascii_case(object_id)) {
+                return Some(object);
+            }
+        }
+
+        let street = normalize(&alarm.address.street);
+        if street.is_empty() {
+            return None;
+        }
+        let city = city_name(&alarm.address.city);
+        self.objects.iter().find(|object| {
+            let object_city = city_name(&object.city);
+            normalize(&object.street) == street && (city.is_empty() || object_city.is_empty() || city == object_city)
+        })
+    }
+}
+
+/// Normalized city without postcode.
+fn city_name(city: &str) -> String {
+    normalize(city.trim_start_matches(|c: char| c.is_ascii_digit() || c.is_whitespace()))
+}
+
+/// Local Einsatzplan/Laufkarte files are attached, so Telegram and Typst can forward them.
+fn load_document(path: &str) -> Option<Attachment> {
+    if path.is_empty() || path.starts_with("http://") || path.starts_with("https://") {
+        return None;
+    }
+
+    match fs::read(path) {
+        Ok(data) => {
+            let name = Path::new(path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| path.to_string());
+            let extension = name.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()).unwrap_or_default();
+            let mime_type = match extension.as_str() {
+                "pdf" => "application/pdf",
+                "png" => "image/png",
+                "jpg" | "jpeg" => "image/jpeg",
+                "gif" => "image/gif",
+                "svg" => "image/svg+xml",
+                _ => "application/octet-stream",
+            };
+            Some(Attachment { name, mime_type: mime_type.to_string(), size: data.len(), path: None, data: data.into() })
+        }
+        Err(e) => {
+            warn!("Could not read object document {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// German name of the OSM `fire_hydrant:type` values.
+fn hydrant_kind(kind: &str) -> String {
+    match kind.to_lowercase().as_str() {
+        "underground" => "Unterflur".to_string(),
+        "pillar" => "Überflur".to_string(),
+        "wall" => "Wandhydrant".to_string(),
+        "pipe" => "Saugrohr".to_string(),
+        "pond" | "water_tank" => "Löschwasserbehälter".to_string(),
+        _ => kind.to_string(),
+    }
+}
+
+/// `100` -> `DN100`, values with a unit are kept.
+fn hydrant_diameter(diameter: &str) -> String {
+    let diameter = diameter.trim();
+    if !diameter.is_empty() && diameter.chars().all(|c| c.is_ascii_digit()) {
+        format!("DN{}", diameter)
+    } else {
+        diameter.to_string()
+    }
+}
+
+/// GeoJSON FeatureCollection of points, e.g. an OSM export of `emergency=fire_hydrant`.
+fn parse_hydrants_geojson(content: &str) -> Result<Vec<Hydrant>, String> {
+    let value: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+    let features = value["features"].as_array().ok_or("no 'features' array")?;
+
+    let mut hydrants = vec![];
+    for feature in features {
+        let coordinates = &feature["geometry"]["coordinates"];
+        let (lon, lat) = match (coordinates[0].as_f64(), coordinates[1].as_f64()) {
+            (Some(lon), Some(lat)) => (lon, lat),
+            _ => continue,
+        };
+
+        let properties = &feature["properties"];
+        let property = |keys: &[&str]| -> String {
+            keys.iter()
+                .find_map(|key| match &properties[*key] {
+                    Value::String(value) => Some(value.clone()),
+                    Value::Number(value) => Some(value.to_string()),
+                    _ => None,
+                })
+                .unwrap_or_default()
+        };
+
+        hydrants.push(Hydrant {
+            kind: hydrant_kind(&property(&["fire_hydrant:type", "type", "art"])),
+            diameter: hydrant_diameter(&property(&["fire_hydrant:diameter", "diameter", "dn"])),
+            reference: property(&["ref", "name", "id"]),
+            lat,
+            lon,
+            distance_m: 0.0,
+        });
+    }
+
+    Ok(hydrants)
+}
+
+/// CSV with a header line (`lat`, `lon`, optional `type`, `diameter`, `ref`), delimiter `,` or `;`.
+fn parse_hydrants_csv(content: &str) -> Result<Vec<Hydrant>, String> {
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next().ok_or("empty file")?;
+    let delimiter = if header.contains(';') { ';' } else { ',' };
+    let split = |line: &str| -> Vec<String> {
+        line.split(delimiter).map(|value| value.trim().trim_matches('"').trim().to_string()).collect()
+    };
+
+    let columns = split(header);
+    let column = |names: &[&str]| columns.iter().position(|column| names.contains(&column.to_lowercase().as_str()));
+    let lat = column(&["lat", "latitude"]).ok_or("column 'lat' missing")?;
+    let lon = column(&["lon", "lng", "longitude"]).ok_or("column 'lon' missing")?;
+    let kind = column(&["type", "art", "fire_hydrant:type"]);
+    let diameter = column(&["diameter", "dn", "fire_hydrant:diameter"]);
+    let reference = column(&["ref", "name", "id"]);
+
+    let mut hydrants = vec![];
+    for (idx, line) in lines.enumerate() {
+        let values = split(line);
+        let value = |column: Option<usize>| column.and_then(|column| values.get(column)).cloned().unwrap_or_default();
+
+        match (value(Some(lat)).replace(',', ".").parse::<f64>(), value(Some(lon)).replace(',', ".").parse::<f64>()) {
+            (Ok(lat), Ok(lon)) => hydrants.push(Hydrant {
+                kind: hydrant_kind(&value(kind)),
+                diameter: hydrant_diameter(&value(diameter)),
+                reference: value(reference),
+                lat,
+                lon,
+                distance_m: 0.0,
+            }),
+            _ => warn!("Hydrants line {}: invalid coordinates, skipped", idx + 2),
+        }
+    }
+
+    Ok(hydrants)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enrichment(hydrants: Vec<Hydrant>, objects: Vec<ObjectInfo>) -> Enrichment {
+        Enrichment { hydrants, hydrant_radius_m: 150.0, max_hydrants: 2, objects, documents: HashMap::new() }
+    }
+
+    #[test]
+    fn nearest_hydrants_within_radius() {
+        // about 11 m per 0.0001° latitude
+        let hydrants = parse_hydrants_csv("lat;lon;ref\n50.0010;8.0;far\n50.0003;8.0;second\n50.0001;8.0;first\n50.0020;8.0;outside\n").unwrap();
+        let nearest = enrichment(hydrants, vec![]).nearest_hydrants(50.0, 8.0);
+        let references: Vec<&str> = nearest.iter().map(|hydrant| hydrant.reference.as_str()).collect();
+        assert_eq!(references, vec!["first", "second"]);
+        assert!((nearest[0].distance_m - 11.1).abs() < 0.5);
+
+        let hydrants = parse_hydrants_csv("lat;lon;ref\n50.0010;8.0;far\n50.0020;8.0;outside\n").unwrap();
+        let nearest = enrichment(hydrants, vec![]).nearest_hydrants(50.0, 8.0);
+        assert_eq!(nearest.len(), 1);
+        assert_eq!(nearest[0].reference, "far");
+    }
+
+    #[test]
+    fn hydrants_geojson() {
+        let geojson = serde_json::json!({ "features": [
+            { "geometry": { "type": "Point", "coordinates": [8.6, 50.1] }, "properties": { "fire_hydrant:type": "underground", "fire_hydrant:diameter": "100", "ref": "H 12" } },
+            { "geometry": { "type": "Point", "coordinates": [8.7, 50.2] }, "properties": { "type": "pillar", "diameter": 150 } },
+            { "geometry": null, "properties": {} },
+        ]});
+        let hydrants = parse_hydrants_geojson(&geojson.to_string()).unwrap();
+        assert_eq!(hydrants.len(), 2);
+        assert_eq!((hydrants[0].lat, hydrants[0].lon), (50.1, 8.6));
+        assert_eq!((hydrants[0].kind.as_str(), hydrants[0].diameter.as_str(), hydrants[0].reference.as_str()), ("Unterflur", "DN100", "H 12"));
+        assert_eq!((hydrants[1].kind.as_str(), hydrants[1].diameter.as_str()), ("Überflur", "DN150"));
+        assert!(parse_hydrants_geojson("{}").is_err());
+    }
+
+    #[test]
+    fn hydrants_csv() {
+        let hydrants = parse_hydrants_csv("\"Latitude\";\"Longitude\";\"Art\";\"DN\"\n\"50,1\";\"8,6\";wall;80 mm\nx;8,6;pipe;\n").unwrap();
+        assert_eq!(hydrants.len(), 1);
+        assert_eq!((hydrants[0].lat, hydrants[0].lon), (50.1, 8.6));
+        assert_eq!((hydrants[0].kind.as_str(), hydrants[0].diameter.as_str()), ("Wandhydrant", "80 mm"));
+        assert!(parse_hydrants_csv("lat;ref\n50.1;H 1\n").is_err());
+    }
+
+    #[test]
+    fn object_by_street_and_city() {
+        let objects: Vec<ObjectInfo> = serde_json::from_value(serde_json::json!([
+            { "id": "OBJ-1", "name": "Rathaus Anderdorf", "street": "Hauptstraße 5", "city": "54321 Anderdorf" },
+            { "id": "OBJ-2", "name": "Rathaus Musterstadt", "street": "Hauptstraße 5", "city": "Musterstadt" },
+        ]))
+        .unwrap();
+        let enrichment = enrichment(vec![], objects);
+
+        let mut alarm = Alarm::new();
+        alarm.address.street = "Hauptstr. 5".to_string();
+        alarm.address.city = "12345 Musterstadt".to_string();
+        enrichment.enrich(&mut alarm);
+        assert_eq!(alarm.object_info.map(|object| object.id), Some("OBJ-2".to_string()));
+
+        let mut alarm = Alarm::new();
+        alarm.address.street = "Hauptstraße 7".to_string();
+        enrichment.enrich(&mut alarm);
+        assert!(alarm.object_info.is_none());
+    }
+
+    #[test]
+    fn object_documents_are_read_at_startup() {
+        let dir = std::env::temp_dir().join(format!("alarm-server-enrichment-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let plan = dir.join("plan.pdf");
+        fs::write(&plan, b"%PDF-1.4").unwrap();
+        let objects = dir.join("objects.json");
+        let objects_json = serde_json::json!([{
+            "id": "OBJ-0815", "name": "Seniorenheim Sonnenhof",
+            "einsatzplan": plan.to_string_lossy(), "laufkarte": "https://example.org/laufkarte.pdf",
+        }]);
+        fs::write(&objects, objects_json.to_string()).unwrap();
+
+        let config: EnrichmentConfig = serde_json::from_value(serde_json::json!({ "objects": objects.to_string_lossy() })).unwrap();
+        let enrichment = Enrichment::new(&config).unwrap();
+        // the alarm does not touch the file system anymore
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut alarm = Alarm::new();
+        alarm.address.object_id = "obj-0815".to_string();
+        enrichment.enrich(&mut alarm);
+
+        assert_eq!(alarm.address.object, "Seniorenheim Sonnenhof");
+        assert_eq!(alarm.attachments.len(), 1);
+        assert_eq!(alarm.attachments[0].name, "plan.pdf");
+        assert_eq!(alarm.attachments[0].mime_type, "application/pdf");
+        assert_eq!(&alarm.attachments[0].data[..], b"%PDF-1.4");
+    }
+}
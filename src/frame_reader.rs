@@ -0,0 +1,186 @@
+use std::time::{Duration, Instant};
+use log::warn;
+
+/// Frame without the delimiter.
+#[derive(Debug, PartialEq)]
+pub struct Frame {
+    pub data: Vec<u8>,
+    /// `false` if the frame was cut at `max_frame_size` or completed by timeout
+    pub delimited: bool,
+}
+
+impl Frame {
+    /// The bytes as received, with the delimiter if there was one.
+    pub fn raw(&self, delimiter: &[u8]) -> Vec<u8> {
+        let mut raw = self.data.clone();
+        if self.delimited {
+            raw.extend_from_slice(delimiter);
+        }
+        raw
+    }
+}
+
+/// Splits a byte stream into frames at the delimiter. A frame is also
+/// complete when no byte arrived for `frame_timeout` (lost delimiter) or
+/// when it reaches `max_frame_size`, so garbage is not glued to the next alarm.
+pub struct FrameReader {
+    delimiter: Vec<u8>,
+    frame_timeout: Option<Duration>,
+    max_frame_size: usize,
+    buffer: Vec<u8>,
+    last_byte: Instant,
+}
+
+impl FrameReader {
+    /// `frame_timeout` and `max_frame_size` of zero disable the limit.
+    pub fn new(delimiter: Vec<u8>, frame_timeout: Duration, max_frame_size: usize) -> Self {
+        Self {
+            delimiter,
+            frame_timeout: (!frame_timeout.is_zero()).then_some(frame_timeout),
+            max_frame_size,
+            buffer: Vec::new(),
+            last_byte: Instant::now(),
+        }
+    }
+
+    /// Adds received bytes, returns the completed frames.
+    pub fn push(&mut self, data: &[u8], now: Instant) -> Vec<Frame> {
+        if data.is_empty() {
+            return self.poll(now).into_iter().collect();
+        }
+
+        // a pause before these bytes ends the previous frame
+        let mut frames: Vec<Frame> = self.poll(now).into_iter().collect();
+        self.last_byte = now;
+        self.buffer.extend_from_slice(data);
+
+        while let Some(pos) = self.find_delimiter() {
+            let data: Vec<u8> = self.buffer.drain(..pos + self.delimiter.len()).take(pos).collect();
+            frames.push(Frame { data, delimited: true });
+        }
+
+        if self.max_frame_size > 0 && self.buffer.len() >= self.max_frame_size {
+            warn!("Frame exceeds {} bytes without delimiter, cut", self.max_frame_size);
+            while self.buffer.len() >= self.max_frame_size {
+                let data = self.buffer.drain(..self.max_frame_size).collect();
+                frames.push(Frame { data, delimited: false });
+            }
+        }
+
+        frames.retain(|frame| !frame.data.is_empty());
+        frames
+    }
+
+    /// The buffered frame if `frame_timeout` passed since its last byte.
+    pub fn poll(&mut self, now: Instant) -> Option<Frame> {
+        let timeout = self.frame_timeout?;
+        if self.buffer.is_empty() || now.duration_since(self.last_byte) < timeout {
+            return None;
+        }
+        warn!("No delimiter after {} bytes within {} ms, frame completed by timeout", self.buffer.len(), timeout.as_millis());
+        Some(Frame { data: std::mem::take(&mut self.buffer), delimited: false })
+    }
+
+    /// The rest of the buffer at the end of a dump.
+    #[cfg(test)]
+    pub fn finish(&mut self) -> Option<Frame> {
+        (!self.buffer.is_empty()).then(|| Frame { data: std::mem::take(&mut self.buffer), delimited: false })
+    }
+
+    fn find_delimiter(&self) -> Option<usize> {
+        if self.delimiter.is_empty() {
+            return None;
+        }
+        self.buffer.windows(self.delimiter.len()).position(|window| window == self.delimiter.as_slice())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reader(frame_timeout_ms: u64, max_frame_size: usize) -> FrameReader {
+        FrameReader::new(b"\r\n".to_vec(), Duration::from_millis(frame_timeout_ms), max_frame_size)
+    }
+
+    fn data(frames: &[Frame]) -> Vec<&[u8]> {
+        frames.iter().map(|frame| frame.data.as_slice()).collect()
+    }
+
+    #[test]
+    fn several_frames_in_one_read() {
+        let mut reader = reader(1000, 0);
+        let frames = reader.push(b"1234567\r\nB3 Wohnhaus\r\n\r\n7654321", Instant::now());
+        assert_eq!(data(&frames), vec![&b"1234567"[..], b"B3 Wohnhaus"]);
+        assert!(frames.iter().all(|frame| frame.delimited));
+        assert_eq!(reader.finish().unwrap().data, b"7654321");
+    }
+
+    #[test]
+    fn delimiter_split_across_reads() {
+        let mut reader = reader(1000, 0);
+        let now = Instant::now();
+        assert!(reader.push(b"B3 Wohnhaus\r", now).is_empty());
+        let frames = reader.push(b"\nTH 1\r\n", now);
+        assert_eq!(data(&frames), vec![&b"B3 Wohnhaus"[..], b"TH 1"]);
+    }
+
+    #[test]
+    fn completed_by_timeout() {
+        let mut reader = reader(1000, 0);
+        let start = Instant::now();
+        assert!(reader.push(b"B3 Wohnhaus", start).is_empty());
+        assert_eq!(reader.poll(start + Duration::from_millis(999)), None);
+
+        let frame = reader.poll(start + Duration::from_millis(1000)).unwrap();
+        assert_eq!(frame, Frame { data: b"B3 Wohnhaus".to_vec(), delimited: false });
+        assert_eq!(reader.poll(start + Duration::from_secs(5)), None);
+    }
+
+    #[test]
+    fn pause_ends_frame_before_new_bytes() {
+        let mut reader = reader(1000, 0);
+        let start = Instant::now();
+        reader.push(b"garbage", start);
+        let frames = reader.push(b"TH 1\r\n", start + Duration::from_secs(2));
+        assert_eq!(data(&frames), vec![&b"garbage"[..], b"TH 1"]);
+        assert_eq!(frames.iter().map(|frame| frame.delimited).collect::<Vec<_>>(), vec![false, true]);
+    }
+
+    #[test]
+    fn no_timeout_when_disabled() {
+        let mut reader = reader(0, 0);
+        let start = Instant::now();
+        reader.push(b"B3", start);
+        assert_eq!(reader.poll(start + Duration::from_secs(3600)), None);
+    }
+
+    #[test]
+    fn cut_at_max_frame_size() {
+        let mut reader = reader(0, 4);
+        let frames = reader.push(b"0123456789\r\nAB", Instant::now());
+        assert_eq!(data(&frames), vec![&b"0123456789"[..]]);
+
+        let frames = reader.push(b"CDEFGHIJ", Instant::now());
+        assert_eq!(data(&frames), vec![&b"ABCD"[..], b"EFGH"]);
+        assert!(frames.iter().all(|frame| !frame.delimited));
+        assert_eq!(reader.finish().unwrap().data, b"IJ");
+    }
+
+    #[test]
+    fn finish_returns_rest_once() {
+        let mut reader = reader(1000, 0);
+        assert_eq!(reader.finish(), None);
+        reader.push(b"TH 1\r\nB3", Instant::now());
+        assert_eq!(reader.finish(), Some(Frame { data: b"B3".to_vec(), delimited: false }));
+        assert_eq!(reader.finish(), None);
+    }
+
+    #[test]
+    fn raw_has_delimiter_only_if_received() {
+        let delimited = Frame { data: b"TH 1".to_vec(), delimited: true };
+        let cut = Frame { data: b"TH 1".to_vec(), delimited: false };
+        assert_eq!(delimited.raw(b"\r\n"), b"TH 1\r\n");
+        assert_eq!(cut.raw(b"\r\n"), b"TH 1");
+    }
+}
@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::fs;
+use std::sync::LazyLock;
+use std::time::Duration;
+use log::{debug, info, warn};
+use regex::Regex;
+use reqwest::{Client, Url};
+use serde_json::Value;
+use crate::alarm::{Address, Coordinates};
+use crate::config::general::GeocodingConfig;
+
+static STREET_SUFFIX: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(strasse|str)\b\.?").unwrap());
+/// `Hauptstraße 5a` / `Hauptstraße 5-7`
+static STREET_HOUSE_NUMBER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(.*?)\s*(\d+\s*[a-zA-Z]?(?:\s*-\s*\d+\s*[a-zA-Z]?)?)$").unwrap());
+/// `12345 Musterstadt`
+static POSTCODE_CITY: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(\d{4,5})?\s*(.*)$").unwrap());
+
+/// One address of the local dataset.
+struct DatasetEntry {
+    house_number: String,
+    postcode: String,
+    city: String,
+    lat: f64,
+    lon: f64,
+}
+
+/// Resolves `Address::street`/`city` to coordinates for alarms without
+/// coordinates. The local dataset is asked first, then the Nominatim endpoint.
+pub struct Geocoder {
+    /// dataset entries by normalized street name
+    streets: HashMap<String, Vec<DatasetEntry>>,
+    nominatim_url: Option<Url>,
+    client: Client,
+}
+
+/// Street, house number, postcode and city of an alarm address.
+struct Query {
+    street: String,
+    house_number: String,
+    postcode: String,
+    city: String,
+}
+
+/// Lower case, `straße`/`strasse`/`str.` unified, single spaces.
+pub fn normalize(value: &str) -> String {
+    let value = value.to_lowercase().replace('ß', "ss");
+    let value = STREET_SUFFIX.replace_all(&value, "str");
+    value.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn normalize_house_number(value: &str) -> String {
+    value.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase()
+}
+
+impl Query {
+    fn new(address: &Address) -> Self {
+        let (street, house_number) = match STREET_HOUSE_NUMBER.captures(address.street.trim()) {
+            Some(captures) => (captures[1].to_string(), captures[2].to_string()),
+            None => (address.street.trim().to_string(), String::new()),
+        };
+
+        let (postcode, city) = match POSTCODE_CITY.captures(address.city.trim()) {
+            Some(captures) => (
+                captures.get(1).map(|m| m.as_str().to_string()).unwrap_or_default(),
+                captures[2].to_string(),
+            ),
+            None => (String::new(), address.city.trim().to_string()),
+        };
+
+        Self { street, house_number, postcode, city }
+    }
+}
+
+impl Geocoder {
+    pub fn new(config: &GeocodingConfig) -> Result<Self, String> {
+        let mut streets: HashMap<String, Vec<DatasetEntry>> = HashMap::new();
+
+        if let Some(path) = &config.dataset {
+            let content = fs::read_to_string(path).map_err(|e| format!("Could not read geocoding dataset {}: {}", path, e))?;
+            let mut count = 0;
+            for (street, entry) in parse_dataset(&content).map_err(|e| format!("Geocoding dataset {}: {}", path, e))? {
+                streets.entry(street).or_default().push(entry);
+                count += 1;
+            }
+            info!("Geocoding dataset {} geladen: {} Adressen", path, count);
+        }
+
+        let nominatim_url = match &config.nominatim_url {
+            Some(url) => {
+                let url = format!("{}/search", url.trim_end_matches('/'));
+                Some(Url::parse(&url).map_err(|e| format!("Invalid nominatim_url '{}': {}", url, e))?)
+            }
+            None => None,
+        };
+
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .user_agent(concat!("alarm-server/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .map_err(|e| format!("Could not create geocoding client: {}", e))?;
+
+        Ok(Self { streets, nominatim_url, client })
+    }
+
+    /// Coordinates for `address`, `None` if it can not be resolved.
+    pub async fn geocode(&self, address: &Address) -> Option<Coordinates> {
+        if address.street.trim().is_empty() {
+            return None;
+        }
+        let query = Query::new(address);
+
+        if let Some(coords) = self.lookup_dataset(&query) {
+            return Some(coords);
+        }
+
+        if let Some(url) = &self.nominatim_url {
+            match self.lookup_nominatim(url, &query).await {
+                Ok(Some(coords)) => return Some(coords),
+                Ok(None) => debug!("Nominatim found no result for {} {}", address.street, address.city),
+                Err(e) => warn!("Geocoding via Nominatim failed: {}", e),
+            }
+        }
+
+        None
+    }
+
+    /// Exact house number first, otherwise the center of all known house
+    /// numbers of the street.
+    fn lookup_dataset(&self, query: &Query) -> Option<Coordinates> {
+        let entries: Vec<&DatasetEntry> = self
+            .streets
+            .get(&normalize(&query.street))?
+            .iter()
+            .filter(|entry| {
+                let city_matches = query.city.is_empty() || entry.city.is_empty() || normalize(&entry.city) == normalize(&query.city);
+                let postcode_matches = query.postcode.is_empty() || entry.postcode.is_empty() || entry.postcode == query.postcode;
+                city_matches && postcode_matches
+            })
+            .collect();
+
+        if entries.is_empty() {
+            return None;
+        }
+
+        let house_number = normalize_house_number(&query.house_number);
+        if let Some(entry) = entries.iter().find(|entry| !house_number.is_empty() && entry.house_number == house_number) {
+            debug!("Geocoded {} {} from dataset", query.street, query.house_number);
+            return Some(Coordinates { lat: Some(entry.lat), lon: Some(entry.lon) });
+        }
+
+        debug!("Geocoded street {} from dataset (house number {} unknown)", query.street, query.house_number);
+        let count = entries.len() as f64;
+        Some(Coordinates {
+            lat: Some(entries.iter().map(|entry| entry.lat).sum::<f64>() / count),
+            lon: Some(entries.iter().map(|entry| entry.lon).sum::<f64>() / count),
+        })
+    }
+
+    async fn lookup_nominatim(&self, url: &Url, query: &Query) -> Result<Option<Coordinates>, String> {
+        let street = format!("{} {}", query.house_number, query.street);
+        let mut params = vec![
+            ("format", "jsonv2"),
+            ("limit", "1"),
+            ("street", street.trim()),
+        ];
+        if !query.city.is_empty() {
+            params.push(("city", query.city.as_str()));
+        }
+        if !query.postcode.is_empty() {
+            params.push(("postalcode", query.postcode.as_str()));
+        }
+
+        let url = Url::parse_with_params(url.as_str(), &params).map_err(|e| e.to_string())?;
+        debug!("Nominatim request: {}", url);
+
+        let response = self.client.get(url).send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+        let results: Value = response.json().await.map_err(|e| e.to_string())?;
+        nominatim_coords(&results)
+    }
+}
+
+/// First result of a Nominatim `jsonv2` search response.
+fn nominatim_coords(results: &Value) -> Result<Option<Coordinates>, String> {
+    let first = match results.as_array().and_then(|results| results.first()) {
+        Some(first) => first,
+        None => return Ok(None),
+    };
+    // Nominatim returns the numbers as strings
+    let number = |key: &str| -> Option<f64> {
+        match &first[key] {
+            Value::String(value) => value.parse().ok(),
+            value => value.as_f64(),
+        }
+    };
+
+    match (number("lat"), number("lon")) {
+        (Some(lat), Some(lon)) => Ok(Some(Coordinates { lat: Some(lat), lon: Some(lon) })),
+        _ => Err(format!("Unexpected response: {}", first)),
+    }
+}
+
+/// Reads a CSV dataset with a header line. Columns are found by name
+/// (`street`, `housenumber`, `postcode`, `city`, `lat`, `lon`, OSM `addr:*`
+/// names work as well), the delimiter is `,` or `;`.
+fn parse_dataset(content: &str) -> Result<Vec<(String, DatasetEntry)>, String> {
+    let mut lines = content.lines().filter(|line| !line.trim().is_empty());
+    let header = lines.next().ok_or("empty file")?;
+    let delimiter = if header.contains(';') { ';' } else { ',' };
+
+    let split = |line: &str| -> Vec<String> {
+        line.split(delimiter).map(|value| value.trim().trim_matches('"').trim().to_string()).collect()
+    };
+
+    let columns = split(header);
+    let column = |names: &[&str]| {
+        columns.iter().position(|column| {
+            let column = column.to_lowercase();
+            let column = column.strip_prefix("addr:").unwrap_or(&column);
+            names.contains(&column)
+        })
+    };
+
+    let street = column(&["street", "strasse", "straße"]).ok_or("column 'street' missing")?;
+    let house_number = column(&["housenumber", "house_number", "hausnummer"]);
+    let postcode = column(&["postcode", "plz"]);
+    let city = column(&["city", "ort"]);
+    let lat = column(&["lat", "latitude"]).ok_or("column 'lat' missing")?;
+    let lon = column(&["lon", "lng", "longitude"]).ok_or("column 'lon' missing")?;
+
+    let mut entries = vec![];
+    for (idx, line) in lines.enumerate() {
+        let values = split(line);
+        let value = |column: Option<usize>| column.and_then(|column| values.get(column)).cloned().unwrap_or_default();
+
+        let (lat, lon) = match (value(Some(lat)).replace(',', ".").parse::<f64>(), value(Some(lon)).replace(',', ".").parse::<f64>()) {
+            (Ok(lat), Ok(lon)) => (lat, lon),
+            _ => {
+                warn!("Geocoding dataset line {}: invalid coordinates, skipped", idx + 2);
+                continue;
+            }
+        };
+
+        entries.push((
+            normalize(&value(Some(street))),
+            DatasetEntry {
+                house_number: normalize_house_number(&value(house_number)),
+                postcode: value(postcode),
+                city: value(city),
+                lat,
+                lon,
+            },
+        ));
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATASET: &str = "\
+addr:street;addr:housenumber;addr:postcode;addr:city;lat;lon
+Hauptstraße;5;12345;Musterstadt;50,1000;8,6000
+Hauptstraße;7 a;12345;Musterstadt;50,1002;8,6004
+Hauptstraße;1;54321;Anderdorf;51.0;9.0
+Waldweg;12;12345;Musterstadt;invalid;8.5
+";
+
+    fn geocoder() -> Geocoder {
+        let mut streets: HashMap<String, Vec<DatasetEntry>> = HashMap::new();
+        for (street, entry) in parse_dataset(DATASET).unwrap() {
+            streets.entry(street).or_default().push(entry);
+        }
+        Geocoder { streets, nominatim_url: None, client: Client::new() }
+    }
+
+    fn lookup(street: &str, city: &str) -> Option<(f64, f64)> {
+        let address = Address { street: street.to_string(), city: city.to_string(), ..Address::new() };
+        geocoder().lookup_dataset(&Query::new(&address)).map(|coords| (coords.lat.unwrap(), coords.lon.unwrap()))
+    }
+
+    #[test]
+    fn dataset_csv() {
+        let entries = parse_dataset(DATASET).unwrap();
+        assert_eq!(entries.len(), 3);
+        let (street, entry) = &entries[1];
+        assert_eq!(street, "hauptstr");
+        assert_eq!((entry.house_number.as_str(), entry.postcode.as_str(), entry.city.as_str()), ("7a", "12345", "Musterstadt"));
+        assert_eq!((entry.lat, entry.lon), (50.1002, 8.6004));
+
+        let entries = parse_dataset("street,lat,lon\n\"Am Markt\",50.2,8.7\n").unwrap();
+        assert_eq!(entries[0].0, "am markt");
+        assert!(parse_dataset("street;lon\nAm Markt;8.7\n").is_err());
+        assert!(parse_dataset("").is_err());
+    }
+
+    #[test]
+    fn exact_house_number() {
+        assert_eq!(lookup("Hauptstr. 7a", "12345 Musterstadt"), Some((50.1002, 8.6004)));
+        assert_eq!(lookup("Hauptstrasse 5", "Musterstadt"), Some((50.1, 8.6)));
+        assert_eq!(lookup("Hauptstraße 1", "54321"), Some((51.0, 9.0)));
+    }
+
+    #[test]
+    fn street_center_without_known_house_number() {
+        let (lat, lon) = lookup("Hauptstraße 99", "12345 Musterstadt").unwrap();
+        assert!((lat - 50.1001).abs() < 1e-9 && (lon - 8.6002).abs() < 1e-9);
+        assert_eq!(lookup("Waldweg 12", "Musterstadt"), None);
+        assert_eq!(lookup("Hauptstraße 5", "99999 Nirgendwo"), None);
+    }
+
+    #[test]
+    fn postcode_and_city() {
+        let query = |street: &str, city: &str| {
+            let query = Query::new(&Address { street: street.to_string(), city: city.to_string(), ..Address::new() });
+            (query.street, query.house_number, query.postcode, query.city)
+        };
+        assert_eq!(query("Hauptstraße 5-7", " 12345 Musterstadt"), ("Hauptstraße".into(), "5-7".into(), "12345".into(), "Musterstadt".into()));
+        assert_eq!(query("Am Markt", "Musterstadt-Süd"), ("Am Markt".into(), "".into(), "".into(), "Musterstadt-Süd".into()));
+        assert_eq!(query("Waldweg 12 b", "1234 Kleinstadt"), ("Waldweg".into(), "12 b".into(), "1234".into(), "Kleinstadt".into()));
+    }
+
+    #[test]
+    fn nominatim_response() {
+        let response = serde_json::json!([{ "place_id": 1, "lat": "50.1106444", "lon": "8.6820917", "display_name": "Römer" }]);
+        let coords = nominatim_coords(&response).unwrap().unwrap();
+        assert_eq!((coords.lat, coords.lon), (Some(50.1106444), Some(8.6820917)));
+        assert!(nominatim_coords(&serde_json::json!([])).unwrap().is_none());
+        assert!(nominatim_coords(&serde_json::json!([{ "lat": "x" }])).is_err());
+    }
+}
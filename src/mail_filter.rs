@@ -0,0 +1,334 @@
+use regex::{Regex, RegexBuilder};
+use crate::config::alarm_sources::{MailConfig, MailRule};
+
+/// A configured match string: `*` alone matches anything, `regex:` prefixes a
+/// regular expression, `*`/`?` inside the string are glob wildcards and
+/// everything else has to match exactly.
+#[derive(Debug, Clone)]
+pub enum TextPattern {
+    Any,
+    Exact(String),
+    ExactIgnoreCase(String),
+    Regex(Regex),
+}
+
+impl TextPattern {
+    pub fn new(pattern: &str, case_insensitive: bool) -> Result<Self, String> {
+        if pattern == "*" {
+            return Ok(TextPattern::Any);
+        }
+
+        if let Some(expression) = pattern.strip_prefix("regex:") {
+            return RegexBuilder::new(expression)
+                .case_insensitive(case_insensitive)
+                .build()
+                .map(TextPattern::Regex)
+                .map_err(|e| format!("Invalid regex '{}': {}", expression, e));
+        }
+
+        if pattern.contains('*') || pattern.contains('?') {
+            let mut expression = String::from("^");
+            for ch in pattern.chars() {
+                match ch {
+                    '*' => expression.push_str(".*"),
+                    '?' => expression.push('.'),
+                    _ => expression.push_str(&regex::escape(&ch.to_string())),
+                }
+            }
+            expression.push('$');
+
+            return RegexBuilder::new(&expression)
+                .case_insensitive(case_insensitive)
+                .build()
+                .map(TextPattern::Regex)
+                .map_err(|e| format!("Invalid pattern '{}': {}", pattern, e));
+        }
+
+        if case_insensitive {
+            Ok(TextPattern::ExactIgnoreCase(pattern.to_lowercase()))
+        } else {
+            Ok(TextPattern::Exact(pattern.to_string()))
+        }
+    }
+
+    /// The legacy `alarm_sender`/`alarm_subject` matching: `*` alone matches
+    /// anything, everything else has to match exactly.
+    pub fn exact(pattern: &str) -> Self {
+        if pattern == "*" {
+            TextPattern::Any
+        } else {
+            TextPattern::Exact(pattern.to_string())
+        }
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        match self {
+            TextPattern::Any => true,
+            TextPattern::Exact(expected) => expected == value,
+            TextPattern::ExactIgnoreCase(expected) => *expected == value.to_lowercase(),
+            TextPattern::Regex(regex) => regex.is_match(value),
+        }
+    }
+}
+
+/// Header information of a mail that is relevant for rule matching.
+pub struct MailHeaders<'a> {
+    pub subject: &'a str,
+    pub sender: &'a str,
+    pub authentication_results: &'a [String],
+    pub received: &'a [String],
+}
+
+/// One compiled `MailRule`.
+#[derive(Clone)]
+pub struct CompiledRule {
+    pub name: String,
+    senders: Vec<TextPattern>,
+    subject: TextPattern,
+    dkim_domain: Option<String>,
+    authserv_ids: Vec<String>,
+    received: Option<Regex>,
+    pub mail_schema: Option<String>,
+}
+
+impl CompiledRule {
+    fn new(name: String, rule: &MailRule, authserv_ids: &[String]) -> Result<Self, String> {
+        if rule.dkim_domain.is_some() && authserv_ids.is_empty() {
+            return Err("dkim_domain needs authserv_ids, otherwise forged Authentication-Results headers would be trusted".to_string());
+        }
+
+        let senders = rule
+            .senders
+            .iter()
+            .map(|sender| TextPattern::new(sender, true))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let subject = TextPattern::new(&rule.subject, false)?;
+
+        let received = match &rule.received {
+            Some(pattern) => Some(
+                Regex::new(pattern).map_err(|e| format!("Invalid received regex '{}': {}", pattern, e))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self {
+            name,
+            senders,
+            subject,
+            dkim_domain: rule.dkim_domain.as_ref().map(|d| d.to_lowercase()),
+            authserv_ids: authserv_ids.to_vec(),
+            received,
+            mail_schema: rule.mail_schema.clone(),
+        })
+    }
+
+    /// Returns `Err` with the reason if the mail does not match this rule.
+    fn check(&self, headers: &MailHeaders) -> Result<(), String> {
+        if !self.senders.is_empty() && !self.senders.iter().any(|p| p.is_match(headers.sender)) {
+            return Err(format!("sender '{}' not allowed", headers.sender));
+        }
+
+        if !self.subject.is_match(headers.subject) {
+            return Err(format!("subject '{}' does not match", headers.subject));
+        }
+
+        if let Some(domain) = &self.dkim_domain {
+            if !headers.authentication_results.iter().any(|header| dkim_passed(header, domain, &self.authserv_ids)) {
+                return Err(format!("no passing DKIM result for {}", domain));
+            }
+        }
+
+        if let Some(received) = &self.received {
+            if !headers.received.iter().any(|r| received.is_match(r)) {
+                return Err("no Received header matches".to_string());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether an `Authentication-Results` header (RFC 8601) added by one of
+/// `authserv_ids` has a `dkim=pass` result signed by exactly `domain`, via
+/// `header.d` or the domain of `header.i`. Headers of other servers may come
+/// from the sender and are ignored.
+fn dkim_passed(header: &str, domain: &str, authserv_ids: &[String]) -> bool {
+    // comments may contain anything, including `;` and `header.d=`
+    let mut without_comments = String::new();
+    let mut depth = 0;
+    for ch in header.chars() {
+        match ch {
+            '(' => depth += 1,
+            ')' if depth > 0 => depth -= 1,
+            _ if depth == 0 => without_comments.push(ch),
+            _ => {}
+        }
+    }
+
+    // the first part is the authserv-id with an optional version, then one part per result
+    let mut parts = without_comments.split(';');
+    let authserv_id = parts.next().and_then(|id| id.split_whitespace().next()).unwrap_or("");
+    if !authserv_ids.iter().any(|id| id.eq_ignore_ascii_case(authserv_id)) {
+        return false;
+    }
+
+    parts.any(|result| {
+        let mut tokens = result.split_whitespace();
+        if !tokens.next().is_some_and(|method| method.eq_ignore_ascii_case("dkim=pass")) {
+            return false;
+        }
+        tokens.filter_map(|token| token.split_once('=')).any(|(property, value)| {
+            let value = value.trim_matches('"');
+            if property.eq_ignore_ascii_case("header.d") {
+                value.eq_ignore_ascii_case(domain)
+            } else if property.eq_ignore_ascii_case("header.i") {
+                value.rsplit_once('@').is_some_and(|(_, signer)| signer.eq_ignore_ascii_case(domain))
+            } else {
+                false
+            }
+        })
+    })
+}
+
+/// Sender/subject rules of a mail source. Without `rules` in the config the
+/// legacy `alarm_sender`/`alarm_subject` pair is used as the only rule, matched
+/// exactly as before the rules existed.
+#[derive(Clone)]
+pub struct MailFilter {
+    rules: Vec<CompiledRule>,
+}
+
+impl MailFilter {
+    pub fn new(config: &MailConfig) -> Result<Self, String> {
+        let rules = if config.rules.is_empty() {
+            vec![CompiledRule {
+                name: "default".to_string(),
+                senders: vec![TextPattern::exact(&config.alarm_sender)],
+                subject: TextPattern::exact(&config.alarm_subject),
+                dkim_domain: None,
+                authserv_ids: vec![],
+                received: None,
+                mail_schema: None,
+            }]
+        } else {
+            config
+                .rules
+                .iter()
+                .enumerate()
+                .map(|(idx, rule)| {
+                    let name = rule.name.clone().unwrap_or_else(|| format!("rule {}", idx + 1));
+                    CompiledRule::new(name.clone(), rule, &config.authserv_ids).map_err(|e| format!("{}: {}", name, e))
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        Ok(Self { rules })
+    }
+
+    /// Whether any rule accepts the sender, used for the SMTP envelope before the mail is received.
+    pub fn allows_sender(&self, sender: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|rule| rule.senders.is_empty() || rule.senders.iter().any(|p| p.is_match(sender)))
+    }
+
+    /// Returns the first rule matching the mail, or the mismatch reasons of all rules.
+    pub fn find_rule(&self, headers: &MailHeaders) -> Result<&CompiledRule, Vec<String>> {
+        let mut reasons = vec![];
+        for rule in &self.rules {
+            match rule.check(headers) {
+                Ok(()) => return Ok(rule),
+                Err(reason) => reasons.push(format!("{}: {}", rule.name, reason)),
+            }
+        }
+        Err(reasons)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(dkim_domain: &str, authentication_results: &[&str]) -> Result<(), String> {
+        let rule = MailRule {
+            name: None,
+            senders: vec![],
+            subject: "*".to_string(),
+            dkim_domain: Some(dkim_domain.to_string()),
+            received: None,
+            mail_schema: None,
+        };
+        let authentication_results: Vec<String> = authentication_results.iter().map(|s| s.to_string()).collect();
+        let headers = MailHeaders { subject: "Alarm", sender: "alarm@leitstelle.de", authentication_results: &authentication_results, received: &[] };
+        CompiledRule::new("test".to_string(), &rule, &["mx.example.org".to_string()]).unwrap().check(&headers)
+    }
+
+    fn legacy_filter(alarm_sender: &str, alarm_subject: &str) -> MailFilter {
+        let config: MailConfig = serde_json::from_value(serde_json::json!({
+            "name": "Leitstelle", "active": true, "host": "localhost", "port": 110, "tls": false, "max_age": 0,
+            "alarm_sender": alarm_sender, "alarm_subject": alarm_subject, "alarm_template_keywords": {}, "mail_schema": "Cobra",
+            "stichwoerter": {}, "ignore_units": [], "polling": false, "polling_interval": 10, "idle": false,
+        }))
+        .unwrap();
+        MailFilter::new(&config).unwrap()
+    }
+
+    fn headers<'a>(sender: &'a str, subject: &'a str) -> MailHeaders<'a> {
+        MailHeaders { subject, sender, authentication_results: &[], received: &[] }
+    }
+
+    #[test]
+    fn dkim_pass_for_domain() {
+        assert!(check("leitstelle.de", &["mx.example.org; dkim=pass header.d=leitstelle.de header.s=mail; spf=pass"]).is_ok());
+        assert!(check("Leitstelle.DE", &["mx.example.org; spf=pass smtp.mailfrom=leitstelle.de; DKIM=pass (2048-bit key) header.d=LEITSTELLE.de"]).is_ok());
+        assert!(check("leitstelle.de", &["mx.example.org; dkim=pass header.i=@leitstelle.de"]).is_ok());
+        assert!(check("leitstelle.de", &["mx.example.org; dkim=pass header.i=alarm@leitstelle.de"]).is_ok());
+    }
+
+    #[test]
+    fn dkim_domain_must_match_exactly() {
+        assert!(check("leitstelle.de", &["mx.example.org; dkim=pass header.d=leitstelle.de.evil.com"]).is_err());
+        assert!(check("leitstelle.de", &["mx.example.org; dkim=pass header.d=evil-leitstelle.de"]).is_err());
+        assert!(check("leitstelle.de", &["mx.example.org; dkim=pass header.i=@leitstelle.de.evil.com"]).is_err());
+    }
+
+    #[test]
+    fn dkim_pass_and_domain_in_the_same_result() {
+        let spoofed = "mx.example.org; dkim=pass header.d=evil.com; dkim=fail header.d=leitstelle.de";
+        assert!(check("leitstelle.de", &[spoofed]).is_err());
+        let other_header = ["mx.example.org; dkim=pass header.d=evil.com", "mx.example.org; dkim=fail header.d=leitstelle.de"];
+        assert!(check("leitstelle.de", &other_header).is_err());
+        assert!(check("leitstelle.de", &["mx.example.org; dkim=fail (header.d=x; dkim=pass header.d=leitstelle.de) header.d=evil.com"]).is_err());
+        assert!(check("leitstelle.de", &["mx.example.org; dkim=neutral header.d=leitstelle.de"]).is_err());
+    }
+
+    #[test]
+    fn dkim_only_from_the_configured_authserv_id() {
+        assert!(check("leitstelle.de", &["MX.Example.org 1; dkim=pass header.d=leitstelle.de"]).is_ok());
+        assert!(check("leitstelle.de", &["(host.example.org) mx.example.org; dkim=pass header.d=leitstelle.de"]).is_ok());
+        // added by the sender before the mail reached our server
+        assert!(check("leitstelle.de", &["mail.leitstelle.de; dkim=pass header.d=leitstelle.de"]).is_err());
+        assert!(check("leitstelle.de", &["mx.example.org.evil.com; dkim=pass header.d=leitstelle.de"]).is_err());
+        assert!(check("leitstelle.de", &["dkim=pass header.d=leitstelle.de"]).is_err());
+
+        let rule: MailRule = serde_json::from_value(serde_json::json!({ "dkim_domain": "leitstelle.de" })).unwrap();
+        assert!(CompiledRule::new("test".to_string(), &rule, &[]).is_err());
+    }
+
+    #[test]
+    fn legacy_sender_and_subject_match_exactly() {
+        let filter = legacy_filter("alarm@leitstelle.de", "Alarm * B3?");
+        assert!(filter.find_rule(&headers("alarm@leitstelle.de", "Alarm * B3?")).is_ok());
+        assert!(filter.find_rule(&headers("alarm@leitstelle.de", "Alarm 4711 B3!")).is_err());
+        assert!(filter.find_rule(&headers("alarm@leitstelle.de", "alarm * b3?")).is_err());
+        assert!(filter.find_rule(&headers("Alarm@Leitstelle.de", "Alarm * B3?")).is_err());
+        assert!(filter.allows_sender("alarm@leitstelle.de"));
+        assert!(!filter.allows_sender("x@leitstelle.de"));
+
+        let filter = legacy_filter("*", "*");
+        assert!(filter.find_rule(&headers("x@evil.com", "anything")).is_ok());
+        let filter = legacy_filter("regex:.*", "regex:.*");
+        assert!(filter.find_rule(&headers("x@evil.com", "anything")).is_err());
+    }
+}
@@ -1,6 +1,7 @@
-use std::collections::VecDeque;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::collections::hash_map::DefaultHasher;
 use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::thread;
 use std::time::Duration;
@@ -8,22 +9,28 @@ use chrono::{DateTime, Local};
 use flume::Sender;
 use imap::{Connection, Session};
 use imap::types::{Fetches, UnsolicitedResponse};
-use mailparse::{parse_mail, MailHeaderMap, ParsedMail};
+use mailparse::{parse_mail, DispositionType, MailHeaderMap, ParsedMail};
 use regex::Regex;
-use crate::alarm::Alarm;
-use crate::config::alarm_sources::MailConfig;
-use crate::mail_parser::{MailParser};
-use crate::mail_parser::sl_secur_cad::SecurCadParser;
-use crate::mail_parser::mock_parser::MockParser;
-use crate::mail_parser::plaintext_parser::PlaintextParser;
+use crate::alarm::{Alarm, Attachment};
+use crate::archive::Archive;
+use crate::coordinates;
+use crate::config::alarm_sources::{MailConfig, MailProtocol};
+use crate::mail_filter::{MailFilter, MailHeaders};
+use crate::mail_parser::{parser_for, MailParser};
+use crate::pop3_client::{Pop3Client, Pop3Result};
+use crate::smtp_server::SmtpServer;
 use log::{debug, error, info, warn};
 
+/// Larger attachments are skipped, alarm faxes and maps are far smaller.
+const MAX_ATTACHMENT_SIZE: usize = 20 * 1024 * 1024;
 
 pub struct MailHandler {
     config: MailConfig,
     send_alarms: Sender<Alarm>,
     debug: bool,
-    mailparser: Box<dyn MailParser>,
+    archive: Option<Archive>,
+    filter: MailFilter,
+    mailparsers: HashMap<String, Box<dyn MailParser>>,
 }
 
 #[derive(Debug, Clone)]
@@ -31,8 +38,11 @@ struct MailData {
     subject: String,
     sender: String,
     date: DateTime<Local>,
+    authentication_results: Vec<String>,
+    received: Vec<String>,
     text_body: String,
     html_body: String,
+    attachments: Vec<Attachment>,
 }
 
 impl MailData {
@@ -48,14 +58,21 @@ impl MailData {
 }
 
 impl MailHandler {
-    pub fn new(config: MailConfig, send_alarms: Sender<Alarm>, debug: bool) -> MailHandler {
-        let mailparser: Box<dyn MailParser> = match config.mail_schema.as_str() {
-            "SL-securCAD" => Box::new(SecurCadParser),
-            "Plaintext" => Box::new(PlaintextParser),
-            _ => Box::new(MockParser),
-        };
+    pub fn new(config: MailConfig, send_alarms: Sender<Alarm>, debug: bool, archive: Option<Archive>) -> Result<MailHandler, String> {
+        let filter = MailFilter::new(&config)?;
+
+        // one parser per schema, rules may select a different one than the source default
+        let mut mailparsers = HashMap::new();
+        let schemas = std::iter::once(config.mail_schema.clone())
+            .chain(config.rules.iter().filter_map(|rule| rule.mail_schema.clone()));
+        for schema in schemas {
+            if let std::collections::hash_map::Entry::Vacant(entry) = mailparsers.entry(schema) {
+                let mailparser = parser_for(entry.key(), &config)?;
+                entry.insert(mailparser);
+            }
+        }
 
-        Self { config, send_alarms, debug, mailparser }
+        Ok(Self { config, send_alarms, debug, archive, filter, mailparsers })
     }
 
     fn connect_imap(config: &MailConfig) -> imap::error::Result<Session<Connection>> {
@@ -82,8 +99,38 @@ impl MailHandler {
 
         info!("{} MailHandler wird gestartet", inbox_name);
 
+        // POP3 has neither IDLE nor UIDs in the IMAP sense, it is always polled
+        if self.config.protocol == MailProtocol::Pop3 {
+            let send_mails = Arc::clone(&send_mails);
+            let config = self.config.clone();
+
+            info!("{} POP3 polling loop wird gestartet", inbox_name);
+
+            let interval = Duration::from_secs(self.config.polling_interval);
+
+            thread::spawn(move || {
+                MailHandler::pop3_loop(config, send_mails, interval);
+            });
+        }
+
+        // Mails are delivered to our own SMTP listener
+        if self.config.protocol == MailProtocol::Smtp {
+            let send_mails = Arc::clone(&send_mails);
+            let config = self.config.clone();
+            let filter = self.filter.clone();
+
+            info!("{} SMTP listener wird gestartet", inbox_name);
+
+            thread::spawn(move || {
+                let server = MailHandler::smtp_server(&config, filter, send_mails);
+                if let Err(e) = server.listen(config.host.as_str(), config.port) {
+                    error!("{} SMTP listener failed: {}", config.name, e);
+                }
+            });
+        }
+
         // Start a thread for the idle loop
-        if self.config.idle {
+        if self.config.protocol == MailProtocol::Imap && self.config.idle {
             let send_mails = Arc::clone(&send_mails);
             let config = self.config.clone();
 
@@ -97,7 +144,7 @@ impl MailHandler {
         }
 
         // Start a thread for the mail checking loop
-        if self.config.polling {
+        if self.config.protocol == MailProtocol::Imap && self.config.polling {
             let send_mails = Arc::clone(&send_mails);
             let config = self.config.clone();
 
@@ -115,19 +162,8 @@ impl MailHandler {
         let mut seen_mails = VecDeque::with_capacity(3);
         loop {
             match recv_mails.recv() {
-                Ok(mail) => {
-                    let hash = mail.calculate_hash();
-                    if seen_mails.contains(&hash) {
-                        info!("Mail already seen - skipping");
-                        continue;
-                    } else {
-                        seen_mails.push_back(hash);
-                        if seen_mails.len() > 3 {
-                            seen_mails.pop_front();
-                        }
-                    }
-
-                    if self.handle_mail(mail) {
+                Ok(raw) => {
+                    if self.receive_mail(&raw, &mut seen_mails) {
                         info!("Mail was handled successfully");
                     } else {
                         warn!("Mail was not handled or an error occurred");
@@ -140,7 +176,42 @@ impl MailHandler {
         }
     }
 
-    fn idle_loop(config: MailConfig, send_mails: Arc<Sender<MailData>>) {
+    /// Archives the raw mail before anything can fail, then parses and
+    /// handles it unless one of the last mails had the same content.
+    fn receive_mail(&self, raw: &[u8], seen_mails: &mut VecDeque<u64>) -> bool {
+        let archived = self.archive.as_ref().and_then(|archive| archive.store(&self.config.name, "", "eml", raw));
+
+        let mail = match MailHandler::read_mail_data(raw) {
+            Ok(mail) => mail,
+            Err(e) => {
+                error!("{}", e);
+                return false;
+            }
+        };
+
+        let hash = mail.calculate_hash();
+        if seen_mails.contains(&hash) {
+            info!("Mail already seen - skipping");
+            return false;
+        }
+        seen_mails.push_back(hash);
+        if seen_mails.len() > 3 {
+            seen_mails.pop_front();
+        }
+
+        self.handle_mail(mail, archived)
+    }
+
+    /// SMTP listener that rejects senders no rule allows and forwards accepted mails.
+    fn smtp_server(config: &MailConfig, filter: MailFilter, send_mails: Arc<Sender<Vec<u8>>>) -> SmtpServer {
+        SmtpServer::new(
+            config.name.clone(),
+            move |sender| filter.allows_sender(sender),
+            move |message| MailHandler::forward_mail(send_mails.as_ref(), &message),
+        )
+    }
+
+    fn idle_loop(config: MailConfig, send_mails: Arc<Sender<Vec<u8>>>) {
         let mut imap_session: Option<Session<Connection>> = None;
         let inbox_name = config.name.clone();
 
@@ -196,11 +267,11 @@ impl MailHandler {
                     continue 'idle_loop;
                 }
             };
-            MailHandler::parse_forward_mail(send_mails.as_ref(), messages);
+            MailHandler::forward_fetched_mail(send_mails.as_ref(), messages);
         }
     }
 
-    fn polling_loop(config: MailConfig, send_mails: Arc<Sender<MailData>>, interval: Duration) {
+    fn polling_loop(config: MailConfig, send_mails: Arc<Sender<Vec<u8>>>, interval: Duration) {
         // Track the most recent message UID we've processed
         let mut last_processed_uid = 0;
         let mut imap_session: Option<Session<Connection>> = None;
@@ -259,7 +330,7 @@ impl MailHandler {
                 };
 
                 info!("{} New mail found (UID: {})...forwarding", inbox_name, latest_uid);
-                MailHandler::parse_forward_mail(send_mails.as_ref(), messages);
+                MailHandler::forward_fetched_mail(send_mails.as_ref(), messages);
 
                 // Update the last processed UID
                 last_processed_uid = latest_uid;
@@ -272,7 +343,79 @@ impl MailHandler {
         }
     }
 
-    fn parse_forward_mail(send_mails: &Sender<MailData>, messages: Fetches) {
+    fn pop3_loop(config: MailConfig, send_mails: Arc<Sender<Vec<u8>>>, interval: Duration) {
+        let inbox_name = config.name.clone();
+        // UIDs of messages that were already forwarded or present at startup
+        let mut seen_uids: Option<HashSet<String>> = None;
+
+        loop {
+            debug!("{} Checking POP3 mailbox for new mail...", inbox_name);
+            if let Err(e) = MailHandler::pop3_check(&config, send_mails.as_ref(), &mut seen_uids) {
+                error!("{} POP3 check failed: {}", inbox_name, e);
+            }
+
+            thread::sleep(interval);
+        }
+    }
+
+    fn pop3_check(
+        config: &MailConfig,
+        send_mails: &Sender<Vec<u8>>,
+        seen_uids: &mut Option<HashSet<String>>,
+    ) -> Pop3Result<()> {
+        let inbox_name = &config.name;
+        let mut client = Pop3Client::connect(config.host.as_str(), config.port, config.tls)?;
+        client.login(config.user.as_str(), config.password.as_str())?;
+
+        let messages = client.uidl()?;
+
+        let new_messages: Vec<(u32, String)> = match seen_uids {
+            Some(seen) => {
+                // forget messages that were removed from the maildrop in the meantime
+                seen.retain(|seen_uid| messages.iter().any(|(_, uid)| uid == seen_uid));
+                messages
+                    .into_iter()
+                    .filter(|(_, uid)| !seen.contains(uid))
+                    .collect()
+            }
+            None => {
+                // first check: like the IMAP polling loop only the newest message is handled,
+                // older ones are only forwarded if they get deleted afterwards anyway
+                if config.delete_after_fetch {
+                    messages
+                } else {
+                    let mut seen = HashSet::new();
+                    let newest = messages.last().cloned();
+                    for (_, uid) in &messages {
+                        seen.insert(uid.clone());
+                    }
+                    if let Some((_, uid)) = &newest {
+                        seen.remove(uid);
+                    }
+                    *seen_uids = Some(seen);
+                    newest.into_iter().collect()
+                }
+            }
+        };
+
+        let seen = seen_uids.get_or_insert_with(HashSet::new);
+
+        for (number, uid) in new_messages {
+            let body = client.retr(number)?;
+            info!("{} New mail found (POP3 UID: {})...forwarding", inbox_name, uid);
+            MailHandler::forward_mail(send_mails, &body);
+
+            if config.delete_after_fetch {
+                client.dele(number)?;
+            } else {
+                seen.insert(uid);
+            }
+        }
+
+        client.quit()
+    }
+
+    fn forward_fetched_mail(send_mails: &Sender<Vec<u8>>, messages: Fetches) {
         let message = match messages.iter().next() {
             Some(message) => message,
             None => {
@@ -289,13 +432,18 @@ impl MailHandler {
             }
         };
 
-        let parsed_mail = match parse_mail(body) {
-            Ok(mail) => mail,
-            Err(e) => {
-                error!("Could not parse mail: {:?}", e);
-                return;
-            }
-        };
+        MailHandler::forward_mail(send_mails, body);
+    }
+
+    /// Raw mails are parsed in the handling loop, after they were archived.
+    fn forward_mail(send_mails: &Sender<Vec<u8>>, body: &[u8]) {
+        if let Err(e) = send_mails.send(body.to_vec()) {
+            error!("Could not forward mail: {:?}", e);
+        }
+    }
+
+    fn read_mail_data(body: &[u8]) -> Result<MailData, String> {
+        let parsed_mail = parse_mail(body).map_err(|e| format!("Could not parse mail: {:?}", e))?;
 
         // Get the subject and sender
         let subject = match parsed_mail.headers.get_first_value("Subject") {
@@ -338,44 +486,74 @@ impl MailHandler {
             }
         };
 
+        let authentication_results = parsed_mail.headers.get_all_values("Authentication-Results");
+        let received = parsed_mail.headers.get_all_values("Received");
+
         let (text_body, html_body) = MailHandler::extract_bodies(&parsed_mail);
+        let attachments = MailHandler::extract_attachments(&parsed_mail, MAX_ATTACHMENT_SIZE);
 
         let mail_data = MailData {
             subject,
             sender,
             date,
+            authentication_results,
+            received,
             text_body,
             html_body,
+            attachments,
         };
 
-        let _ = send_mails.send(mail_data);
+        Ok(mail_data)
+    }
+
+    /// Runs a raw RFC822 message through validation and the parser without
+    /// dispatching it. The `max_age` check is skipped, replayed mails are old.
+    pub fn replay(&self, body: &[u8]) -> Result<Alarm, String> {
+        let mail_data = MailHandler::read_mail_data(body)?;
+        info!("Replaying mail: {}: <{}>", mail_data.subject, mail_data.sender);
+        self.build_alarm(&mail_data, false)
     }
-    fn handle_mail(&self, mail_data: MailData) -> bool {
+
+    /// `archived` is the archive file of the mail, named after the alarm id once it is known.
+    fn handle_mail(&self, mail_data: MailData, archived: Option<PathBuf>) -> bool {
         info!("Handling mail: {}: <{}>", mail_data.subject, mail_data.sender);
 
-        // Validate mail
-        if (self.config.alarm_subject != "*") && (mail_data.subject != self.config.alarm_subject) {
-            warn!(
-                "Subject mismatch: '{}' != '{}'",
-                mail_data.subject, self.config.alarm_subject
-            );
-            return false;
-        }
-        if (self.config.alarm_sender != "*") && (mail_data.sender != self.config.alarm_sender) {
-            warn!(
-                "Sender mismatch: '{}' != '{}'",
-                mail_data.sender, self.config.alarm_sender
-            );
-            return false;
+        match self.build_alarm(&mail_data, true) {
+            Ok(alarm) => {
+                if let (Some(archive), Some(path)) = (&self.archive, archived) {
+                    archive.attach_id(&path, &alarm.id);
+                }
+                self.send_alarms.send(alarm).ok();
+                true
+            }
+            Err(e) => {
+                warn!("{}", e);
+                false
+            }
         }
+    }
 
-        if self.config.max_age > 0  {
+    fn build_alarm(&self, mail_data: &MailData, check_age: bool) -> Result<Alarm, String> {
+
+        // Validate mail
+        let headers = MailHeaders {
+            subject: &mail_data.subject,
+            sender: &mail_data.sender,
+            authentication_results: &mail_data.authentication_results,
+            received: &mail_data.received,
+        };
+        let rule = self
+            .filter
+            .find_rule(&headers)
+            .map_err(|reasons| format!("Mail does not match any rule: {}", reasons.join("; ")))?;
+        debug!("Mail matches rule '{}'", rule.name);
+
+        if check_age && self.config.max_age > 0  {
             let now = Local::now();
             let mail_date = mail_data.date;
             let age = now.signed_duration_since(mail_date).num_seconds() as u64;
             if age > self.config.max_age {
-                warn!("Mail is too old: {}s > {}s", age, self.config.max_age);
-                return false;
+                return Err(format!("Mail is too old: {}s > {}s", age, self.config.max_age));
             }
         }
 
@@ -383,16 +561,51 @@ impl MailHandler {
         alarm.alarm_source(self.config.name.clone());
         alarm.origin = self.config.name.clone();
 
-        match self.mailparser.parse(&mail_data.text_body, &mail_data.html_body, &mut alarm, self.config.clone()) {
-            Ok(_) => {
-                self.send_alarms.send(alarm).ok();
-                true
-            }
-            Err(e) => {
-                error!("Could not parse mail: {}", e);
-                false
+        let mail_schema = rule.mail_schema.as_ref().unwrap_or(&self.config.mail_schema);
+        let mailparser = self
+            .mailparsers
+            .get(mail_schema)
+            .ok_or_else(|| format!("No parser for mail schema '{}'", mail_schema))?;
+
+        let mut report = mailparser
+            .parse(&mail_data.text_body, &mail_data.html_body, &mut alarm, self.config.clone())
+            .map_err(|e| format!("Could not parse mail: {}", e))?;
+
+        // no lat/lon in the mail, derive them from the grid reference
+        if alarm.address.coords.lat.is_none() && !alarm.address.utm.trim().is_empty() {
+            match coordinates::parse(&alarm.address.utm) {
+                Ok(coords) => {
+                    debug!("Coordinates derived from '{}': {:?}", alarm.address.utm, coords);
+                    alarm.address.set_coords(coords);
+                    report.missing.retain(|field| field != "coords");
+                    report.field("coords", true);
+                }
+                Err(e) => report.warn(format!("Could not convert '{}' to coordinates: {}", alarm.address.utm, e)),
             }
         }
+
+        info!(
+            "Parsed mail with schema '{}', found: [{}], missing: [{}]",
+            mail_schema,
+            report.found.join(", "),
+            report.missing.join(", ")
+        );
+        for warning in &report.warnings {
+            warn!("Parser warning ({}): {}", mail_schema, warning);
+        }
+        alarm.parse_report = report;
+
+        // a parser that did not recognize anything must not trigger an empty alarm
+        if alarm.title.trim().is_empty() && alarm.text.trim().is_empty() {
+            return Err(format!("Parsed alarm is empty (no title or text), schema '{}'", mail_schema));
+        }
+
+        for attachment in &mail_data.attachments {
+            info!("Mail attachment: {} ({}, {} bytes)", attachment.name, attachment.mime_type, attachment.size);
+            alarm.add_attachment(attachment.clone());
+        }
+
+        Ok(alarm)
     }
 
 
@@ -434,4 +647,356 @@ impl MailHandler {
         traverse_parts(parsed_mail, &mut text_body, &mut html_body);
         (text_body, html_body)
     }
+
+    /// Collects all parts that are attachments: explicitly marked as such or
+    /// anything that is neither text nor a multipart container. Attachments
+    /// larger than `max_size` bytes are skipped.
+    fn extract_attachments(parsed_mail: &ParsedMail, max_size: usize) -> Vec<Attachment> {
+        let mut attachments = vec![];
+
+        for part in parsed_mail.parts() {
+            let mime_type = part.ctype.mimetype.to_lowercase();
+            if mime_type.starts_with("multipart/") {
+                continue;
+            }
+
+            let disposition = part.get_content_disposition();
+            let is_attachment = disposition.disposition == DispositionType::Attachment
+                || !(mime_type == "text/plain" || mime_type == "text/html");
+            if !is_attachment {
+                continue;
+            }
+
+            let data = match part.get_body_raw() {
+                Ok(data) => data,
+                Err(e) => {
+                    warn!("Could not decode attachment: {:?}", e);
+                    continue;
+                }
+            };
+
+            let name = disposition
+                .params
+                .get("filename")
+                .or_else(|| part.ctype.params.get("name"))
+                .cloned()
+                .unwrap_or_else(|| format!("attachment_{}", attachments.len() + 1));
+
+            if data.len() > max_size {
+                warn!("Attachment {} skipped: {} bytes > {} bytes", name, data.len(), max_size);
+                continue;
+            }
+
+            attachments.push(Attachment {
+                name,
+                mime_type,
+                size: data.len(),
+                path: None,
+                data: data.into(),
+            });
+        }
+
+        attachments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::{TcpListener, TcpStream};
+
+    /// SMTP client side of the dialog, returns the last line of each reply.
+    struct SmtpClient {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl SmtpClient {
+        fn connect(port: u16) -> (Self, String) {
+            let stream = TcpStream::connect(("127.0.0.1", port)).unwrap();
+            stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+            let mut client = SmtpClient { reader: BufReader::new(stream.try_clone().unwrap()), writer: stream };
+            let greeting = client.reply();
+            (client, greeting)
+        }
+
+        fn reply(&mut self) -> String {
+            loop {
+                let mut line = String::new();
+                self.reader.read_line(&mut line).unwrap();
+                // `250-...` continues, `250 ...` is the last line
+                if line.as_bytes().get(3) != Some(&b'-') {
+                    return line.trim_end().to_string();
+                }
+            }
+        }
+
+        fn command(&mut self, line: &str) -> String {
+            self.writer.write_all(format!("{}\r\n", line).as_bytes()).unwrap();
+            self.reply()
+        }
+
+        /// DATA with dot-stuffing and the terminating dot.
+        fn data(&mut self, message: &[u8]) -> String {
+            assert!(self.command("DATA").starts_with("354"));
+            for line in String::from_utf8_lossy(message).lines() {
+                let line = if line.starts_with('.') { format!(".{}", line) } else { line.to_string() };
+                self.writer.write_all(format!("{}\r\n", line).as_bytes()).unwrap();
+            }
+            self.command(".")
+        }
+    }
+
+    fn config() -> MailConfig {
+        serde_json::from_value(serde_json::json!({
+            "name": "SMTP", "active": true, "protocol": "SMTP", "host": "127.0.0.1", "port": 0, "tls": false, "max_age": 0,
+            "alarm_sender": "alarm@leitstelle.example", "alarm_subject": "*", "alarm_template_keywords": {}, "mail_schema": "Cobra",
+            "stichwoerter": {}, "ignore_units": [], "polling": false, "polling_interval": 0, "idle": false,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn smtp_mail_reaches_the_handler() {
+        let (send_alarms, recv_alarms) = flume::unbounded();
+        let handler = MailHandler::new(config(), send_alarms, false, None).unwrap();
+        let (send_mails, recv_mails) = flume::unbounded();
+        let server = MailHandler::smtp_server(&handler.config, handler.filter.clone(), Arc::new(send_mails));
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || server.serve(listener));
+
+        let message = std::fs::read(format!("{}/fixtures/mail/cobra.eml", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        let (mut client, greeting) = SmtpClient::connect(port);
+        assert!(greeting.starts_with("220"));
+        assert!(client.command("EHLO leitstelle.example").starts_with("250"));
+        assert!(client.command("MAIL FROM:<alarm@leitstelle.example> SIZE=2000").starts_with("250"));
+        assert!(client.command("RCPT TO:<alarm@feuerwehr.example>").starts_with("250"));
+        assert!(client.data(&message).starts_with("250"));
+        assert!(client.command("QUIT").starts_with("221"));
+
+        let raw = recv_mails.recv_timeout(Duration::from_secs(5)).unwrap();
+        let mail = MailHandler::read_mail_data(&raw).unwrap();
+        assert_eq!(mail.sender, "alarm@leitstelle.example");
+        assert_eq!(mail.subject, "Alarm 2026-004711 B3");
+        assert!(handler.receive_mail(&raw, &mut VecDeque::new()));
+        let alarm = recv_alarms.try_recv().unwrap();
+        assert_eq!(alarm.id, "2026-004711");
+        assert_eq!(alarm.address.street, "Hauptstraße 5");
+    }
+
+    #[test]
+    fn smtp_rejects_disallowed_sender() {
+        let handler = MailHandler::new(config(), flume::unbounded().0, false, None).unwrap();
+        let (send_mails, recv_mails) = flume::unbounded();
+        let server = MailHandler::smtp_server(&handler.config, handler.filter.clone(), Arc::new(send_mails));
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        thread::spawn(move || server.serve(listener));
+
+        let (mut client, _) = SmtpClient::connect(port);
+        assert!(client.command("HELO spam.example").starts_with("250"));
+        assert!(client.command("MAIL FROM:<alarm@spam.example>").starts_with("550"));
+        assert!(client.command("RCPT TO:<alarm@feuerwehr.example>").starts_with("503"));
+        assert!(client.command("DATA").starts_with("503"));
+        assert!(client.command("QUIT").starts_with("221"));
+        assert!(recv_mails.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+
+    #[test]
+    fn mails_are_archived_before_parsing() {
+        let directory = std::env::temp_dir().join(format!("alarm-server-mail-archive-{}", std::process::id()));
+        let archive = Archive::new(&crate::config::general::ArchiveConfig { directory: directory.display().to_string(), max_age_days: 0, max_files: 0 });
+        let handler = MailHandler::new(config(), flume::unbounded().0, false, Some(archive)).unwrap();
+        let mut seen_mails = VecDeque::new();
+
+        let message = std::fs::read(format!("{}/fixtures/mail/cobra.eml", env!("CARGO_MANIFEST_DIR"))).unwrap();
+        assert!(handler.receive_mail(&message, &mut seen_mails));
+        assert!(!handler.receive_mail(b"\xff\xfe not a mail", &mut seen_mails));
+
+        let mut names: Vec<String> = std::fs::read_dir(directory.join("SMTP"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().split_once('_').unwrap().1.split_once('_').unwrap().1.to_string())
+            .collect();
+        names.sort();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!(names, vec!["2026-004711.eml", "no-id.eml"]);
+    }
+
+    #[test]
+    fn attachments_of_a_multipart_mail() {
+        let message = "\
+From: alarm@leitstelle.example\r
+Subject: Alarm\r
+Content-Type: multipart/mixed; boundary=\"outer\"\r
+\r
+--outer\r
+Content-Type: multipart/alternative; boundary=\"inner\"\r
+\r
+--inner\r
+Content-Type: text/plain; charset=utf-8\r
+\r
+Einsatz B3\r
+--inner\r
+Content-Type: text/html; charset=utf-8\r
+\r
+<p>Einsatz B3</p>\r
+--inner--\r
+--outer\r
+Content-Type: application/pdf\r
+Content-Disposition: attachment; filename=\"Alarmfax.pdf\"\r
+Content-Transfer-Encoding: base64\r
+\r
+JVBERi0xLjQK\r
+--outer\r
+Content-Type: image/PNG; name=\"karte.png\"\r
+Content-Transfer-Encoding: base64\r
+\r
+iVBORw0KGgo=\r
+--outer\r
+Content-Type: text/plain\r
+Content-Disposition: attachment\r
+\r
+Einsatzplan als Text\r
+--outer\r
+Content-Type: application/octet-stream; name=\"gross.bin\"\r
+\r
+0123456789012345678901234567890123456789\r
+--outer--\r
+";
+        let parsed_mail = parse_mail(message.as_bytes()).unwrap();
+        let attachments = MailHandler::extract_attachments(&parsed_mail, 32);
+        let summary: Vec<(&str, &str, usize)> =
+            attachments.iter().map(|attachment| (attachment.name.as_str(), attachment.mime_type.as_str(), attachment.size)).collect();
+        assert_eq!(
+            summary,
+            vec![("Alarmfax.pdf", "application/pdf", 9), ("karte.png", "image/png", 8), ("attachment_3", "text/plain", 20)]
+        );
+        assert_eq!(&attachments[0].data[..], b"%PDF-1.4\n");
+        assert_eq!(attachments[0].path, None);
+
+        let (text_body, html_body) = MailHandler::extract_bodies(&parsed_mail);
+        assert!(text_body.contains("Einsatz B3"));
+        assert!(html_body.contains("<p>Einsatz B3</p>"));
+        assert_eq!(MailHandler::extract_attachments(&parsed_mail, MAX_ATTACHMENT_SIZE).len(), 4);
+    }
+
+    /// Maildrop of the scripted POP3 server: unique id and message.
+    type Maildrop = Arc<std::sync::Mutex<Vec<(String, Vec<u8>)>>>;
+
+    /// POP3 server answering one session per connection, messages marked with
+    /// DELE are removed at QUIT. Returns the port and the log of all commands.
+    fn pop3_server(maildrop: Maildrop) -> (u16, Arc<std::sync::Mutex<Vec<String>>>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let log = Arc::new(std::sync::Mutex::new(vec![]));
+        let commands = log.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut writer = stream.unwrap();
+                let mut reader = BufReader::new(writer.try_clone().unwrap());
+                let mut deleted = vec![];
+                writer.write_all(b"+OK POP3 ready\r\n").unwrap();
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap() == 0 {
+                        break;
+                    }
+                    let line = line.trim_end().to_string();
+                    commands.lock().unwrap().push(line.clone());
+                    let mut messages = maildrop.lock().unwrap();
+                    let (command, argument) = line.split_once(' ').unwrap_or((&line, ""));
+                    let number = argument.parse::<usize>().ok().filter(|n| (1..=messages.len()).contains(n));
+                    let reply = match (command, number) {
+                        ("USER", _) | ("PASS", _) => "+OK\r\n".to_string(),
+                        ("UIDL", _) => {
+                            let list: String = messages.iter().enumerate().map(|(idx, (uid, _))| format!("{} {}\r\n", idx + 1, uid)).collect();
+                            format!("+OK\r\n{}.\r\n", list)
+                        }
+                        ("RETR", Some(number)) => {
+                            let message = String::from_utf8_lossy(&messages[number - 1].1).to_string();
+                            let lines: String = message.lines().map(|l| if l.starts_with('.') { format!(".{}\r\n", l) } else { format!("{}\r\n", l) }).collect();
+                            format!("+OK\r\n{}.\r\n", lines)
+                        }
+                        ("DELE", Some(number)) => {
+                            deleted.push(messages[number - 1].0.clone());
+                            "+OK\r\n".to_string()
+                        }
+                        ("QUIT", _) => {
+                            messages.retain(|(uid, _)| !deleted.contains(uid));
+                            writer.write_all(b"+OK bye\r\n").unwrap();
+                            break;
+                        }
+                        _ => "-ERR\r\n".to_string(),
+                    };
+                    writer.write_all(reply.as_bytes()).unwrap();
+                }
+            }
+        });
+        (port, log)
+    }
+
+    fn pop3_config(port: u16, delete_after_fetch: bool) -> MailConfig {
+        serde_json::from_value(serde_json::json!({
+            "name": "POP3", "active": true, "protocol": "POP3", "user": "alarm", "password": "secret",
+            "host": "127.0.0.1", "port": port, "tls": false, "max_age": 0, "delete_after_fetch": delete_after_fetch,
+            "alarm_sender": "alarm@leitstelle.example", "alarm_subject": "*", "alarm_template_keywords": {}, "mail_schema": "Cobra",
+            "stichwoerter": {}, "ignore_units": [], "polling": true, "polling_interval": 1, "idle": false,
+        }))
+        .unwrap()
+    }
+
+    fn message(id: u32) -> (String, Vec<u8>) {
+        (format!("uid-{}", id), format!("Subject: Alarm {}\r\n\r\nText\r\n.Zeile mit Punkt\r\n", id).into_bytes())
+    }
+
+    #[test]
+    fn pop3_first_poll_takes_the_newest_message_only() {
+        let maildrop: Maildrop = Arc::new(std::sync::Mutex::new(vec![message(1), message(2), message(3)]));
+        let (port, log) = pop3_server(maildrop.clone());
+        let config = pop3_config(port, false);
+        let (send_mails, recv_mails) = flume::unbounded();
+        let mut seen_uids = None;
+
+        MailHandler::pop3_check(&config, &send_mails, &mut seen_uids).unwrap();
+        assert_eq!(recv_mails.drain().collect::<Vec<_>>(), vec![message(3).1]);
+
+        maildrop.lock().unwrap().push(message(4));
+        MailHandler::pop3_check(&config, &send_mails, &mut seen_uids).unwrap();
+        assert_eq!(recv_mails.drain().collect::<Vec<_>>(), vec![message(4).1]);
+
+        // seen messages are not fetched again, also when older ones are removed
+        maildrop.lock().unwrap().remove(0);
+        MailHandler::pop3_check(&config, &send_mails, &mut seen_uids).unwrap();
+        assert!(recv_mails.is_empty());
+        assert_eq!(seen_uids.unwrap().len(), 3);
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.iter().filter(|command| command.starts_with("RETR")).collect::<Vec<_>>(), vec!["RETR 3", "RETR 4"]);
+        assert!(!log.iter().any(|command| command.starts_with("DELE")));
+        assert_eq!(log[..2], ["USER alarm", "PASS secret"]);
+    }
+
+    #[test]
+    fn pop3_delete_after_fetch() {
+        let maildrop: Maildrop = Arc::new(std::sync::Mutex::new(vec![message(1), message(2)]));
+        let (port, log) = pop3_server(maildrop.clone());
+        let config = pop3_config(port, true);
+        let (send_mails, recv_mails) = flume::unbounded();
+        let mut seen_uids = None;
+
+        // every message is fetched and deleted, also on the first poll
+        MailHandler::pop3_check(&config, &send_mails, &mut seen_uids).unwrap();
+        assert_eq!(recv_mails.drain().collect::<Vec<_>>(), vec![message(1).1, message(2).1]);
+        assert!(maildrop.lock().unwrap().is_empty());
+
+        maildrop.lock().unwrap().push(message(3));
+        MailHandler::pop3_check(&config, &send_mails, &mut seen_uids).unwrap();
+        assert_eq!(recv_mails.drain().collect::<Vec<_>>(), vec![message(3).1]);
+        assert!(maildrop.lock().unwrap().is_empty());
+
+        let log = log.lock().unwrap();
+        assert_eq!(log.iter().filter(|command| command.starts_with("DELE")).collect::<Vec<_>>(), vec!["DELE 1", "DELE 2", "DELE 1"]);
+    }
 }
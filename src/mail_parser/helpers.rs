@@ -1,10 +1,76 @@
+use crate::alarm::ParseReport;
+use encoding_rs::Encoding;
 use quoted_printable::decode;
+use quoted_printable::ParseMode::{Robust, Strict};
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
 use std::collections::HashMap;
-use quoted_printable::ParseMode::Strict;
-use scraper::{Html, Selector};
+use std::sync::LazyLock;
 
+static QP_ESCAPE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"=[0-9A-Fa-f]{2}").unwrap());
 
-pub fn parse_tables(body: &str) -> HashMap<String, Vec<String>> {
+/// Decodes quoted-printable leftovers in a table cell. Bytes that are not valid
+/// UTF-8 are decoded with `charset`, problems are returned as warning.
+fn decode_cell(text: &str, charset: &'static Encoding) -> (String, Option<String>) {
+    let mut warnings = vec![];
+
+    let decoded = match decode(text, Strict) {
+        Ok(decoded) => decoded,
+        Err(_) => {
+            // plain text with a '=' is fine, broken escapes are decoded as far as possible
+            if !QP_ESCAPE.is_match(text) {
+                return (text.to_string(), None);
+            }
+            warnings.push(format!(
+                "Malformed quoted-printable in table cell '{}'",
+                text
+            ));
+            match decode(text, Robust) {
+                Ok(decoded) => decoded,
+                Err(_) => return (text.to_string(), warnings.pop()),
+            }
+        }
+    };
+
+    let decoded = match String::from_utf8(decoded) {
+        Ok(decoded) => decoded,
+        Err(e) => {
+            let (decoded, _, had_errors) = charset.decode(e.as_bytes());
+            warnings.push(format!(
+                "Table cell '{}' is not UTF-8, decoded as {}{}",
+                text,
+                charset.name(),
+                if had_errors {
+                    " with replacement characters"
+                } else {
+                    ""
+                }
+            ));
+            decoded.trim().to_string()
+        }
+    };
+
+    let warning = (!warnings.is_empty()).then(|| warnings.join(", "));
+    (decoded, warning)
+}
+
+fn cell_text(cell: ElementRef) -> String {
+    // soft line breaks are sometimes left in the html part
+    cell.text()
+        .collect::<String>()
+        .replace("=\r\n", "")
+        .replace("=\n", "")
+        .trim()
+        .to_string()
+}
+
+/// Key/value pairs of all html tables in `body`. The first cell of a row is the
+/// key, the other cells are its values; rows without a key continue the previous one.
+pub fn parse_tables(
+    body: &str,
+    charset: &'static Encoding,
+    report: &mut ParseReport,
+) -> HashMap<String, Vec<String>> {
     let document = Html::parse_document(body);
     let table_selector = Selector::parse("table").unwrap();
     let row_selector = Selector::parse("tr").unwrap();
@@ -13,7 +79,6 @@ pub fn parse_tables(body: &str) -> HashMap<String, Vec<String>> {
     let mut result = HashMap::new();
     let mut current_key = String::new();
 
-    // Select your table and row selectors (assumed to be defined)
     for table in document.select(&table_selector) {
         for row in table.select(&row_selector) {
             let mut row_data = vec![];
@@ -22,39 +87,38 @@ pub fn parse_tables(body: &str) -> HashMap<String, Vec<String>> {
 
             // Process the first cell as a key (decoded with quoted-printable)
             if let Some(first_cell) = cells.next() {
-                let key = first_cell.text().collect::<String>().trim().to_string();
+                let key = cell_text(first_cell);
                 if !key.is_empty() {
-                    let decoded_key = match decode(&key, Strict) {
-                        Ok(decoded) => decoded,
-                        Err(_) => key.as_bytes().to_vec(),
-                    };
-                    let key_str = String::from_utf8(decoded_key).expect("Invalid UTF-8 in key");
-                    current_key = key_str;
+                    let (key, warning) = decode_cell(&key, charset);
+                    if let Some(warning) = warning {
+                        report.warn(warning);
+                    }
+                    current_key = key;
                 }
             }
 
             // Process remaining cells as data (decoded with quoted-printable)
             for cell in cells {
-                let cell_text = cell.text().collect::<String>().replace(
-                    &['\n', '\r', '\t'][..],
-                    ""
-                ).trim().to_string();
-                let decoded_cell = match decode(&cell_text, Strict) {
-                    Ok(decoded) => decoded,
-                    Err(_) => cell_text.as_bytes().to_vec(),
-                };
-                let cell_str = String::from_utf8(decoded_cell).expect("Invalid UTF-8 in cell");
-                row_data.push(cell_str);
+                let value = cell_text(cell).replace(&['\n', '\r', '\t'][..], "");
+                let (value, warning) = decode_cell(value.trim(), charset);
+                if let Some(warning) = warning {
+                    report.warn(warning);
+                }
+                row_data.push(value);
             }
 
-            result.entry(current_key.clone()).or_insert_with(Vec::new).extend(row_data);
+            result
+                .entry(current_key.clone())
+                .or_insert_with(Vec::new)
+                .extend(row_data);
         }
     }
 
     result
 }
 
-pub fn get_table_key_order(body: &str) -> Vec<String> {
+/// Row keys in document order, decoded the same way as in `parse_tables`.
+pub fn get_table_key_order(body: &str, charset: &'static Encoding) -> Vec<String> {
     let document = Html::parse_document(body);
     let table_selector = Selector::parse("table").unwrap();
     let row_selector = Selector::parse("tr").unwrap();
@@ -68,13 +132,69 @@ pub fn get_table_key_order(body: &str) -> Vec<String> {
 
             // Process the first cell as a key
             if let Some(first_cell) = cells.next() {
-                let key = first_cell.text().collect::<String>().trim().to_string();
+                let key = cell_text(first_cell);
                 if !key.is_empty() {
-                    result.push(key);
+                    result.push(decode_cell(&key, charset).0);
                 }
             }
         }
     }
 
     result
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{ISO_8859_15, UTF_8, WINDOWS_1252};
+
+    #[test]
+    fn quoted_printable_cell() {
+        assert_eq!(decode_cell("Hauptstra=C3=9Fe 5", WINDOWS_1252), ("Hauptstraße 5".to_string(), None));
+        assert_eq!(decode_cell("B3 Wohnhaus", WINDOWS_1252), ("B3 Wohnhaus".to_string(), None));
+        // a '=' that is no escape is kept
+        assert_eq!(decode_cell("Etage = 2", WINDOWS_1252), ("Etage = 2".to_string(), None));
+    }
+
+    #[test]
+    fn malformed_quoted_printable_cell() {
+        let (decoded, warning) = decode_cell("Stra=C3=9Fe =Z1", WINDOWS_1252);
+        assert_eq!(decoded, "Straße =Z1");
+        assert!(warning.unwrap().contains("Malformed quoted-printable"));
+    }
+
+    #[test]
+    fn cp1252_cell() {
+        // 0x80 is the euro sign and 0x84 a low quotation mark only in Windows-1252
+        let (decoded, warning) = decode_cell("Schaden 5000 =80, =84Keller=93", WINDOWS_1252);
+        assert_eq!(decoded, "Schaden 5000 €, „Keller“");
+        assert!(warning.unwrap().contains("windows-1252"));
+    }
+
+    #[test]
+    fn latin1_cell() {
+        let (decoded, warning) = decode_cell("M=FChlenweg, Gr=F6=DFe", WINDOWS_1252);
+        assert_eq!(decoded, "Mühlenweg, Größe");
+        assert!(warning.is_some());
+
+        let (decoded, _) = decode_cell("=A4 Pfand", ISO_8859_15);
+        assert_eq!(decoded, "€ Pfand");
+
+        // not decodable as UTF-8 with replacement characters
+        let (decoded, warning) = decode_cell("M=FChlenweg", UTF_8);
+        assert_eq!(decoded, "M\u{FFFD}hlenweg");
+        assert!(warning.unwrap().contains("replacement characters"));
+    }
+
+    #[test]
+    fn tables_with_continuation_rows() {
+        let body = "<table><tr><td>Einsatzort</td><td>Hauptstra=C3=9Fe 5</td></tr>\
+            <tr><td></td><td>Musterstadt</td></tr><tr><td>Stichwort</td><td>B3</td></tr></table>";
+        let mut report = ParseReport::default();
+        let tables = parse_tables(body, WINDOWS_1252, &mut report);
+        assert_eq!(tables["Einsatzort"], vec!["Hauptstraße 5", "Musterstadt"]);
+        assert_eq!(tables["Stichwort"], vec!["B3"]);
+        assert!(report.warnings.is_empty());
+        assert_eq!(get_table_key_order(body, WINDOWS_1252), vec!["Einsatzort", "Stichwort"]);
+    }
+}
@@ -1,11 +1,50 @@
 pub mod sl_secur_cad;
-pub mod mock_parser;
 mod helpers;
 pub mod plaintext_parser;
+pub mod table_parser;
+pub mod preset_parser;
 
-use crate::alarm::Alarm;
+use encoding_rs::{Encoding, WINDOWS_1252};
+use crate::alarm::{Alarm, ParseReport};
 use crate::config::alarm_sources::MailConfig;
+use crate::mail_parser::plaintext_parser::PlaintextParser;
+use crate::mail_parser::preset_parser::{PresetParser, COBRA, ELDIS, FIREBOARD};
+use crate::mail_parser::sl_secur_cad::SecurCadParser;
+use crate::mail_parser::table_parser::TableParser;
 
 pub trait MailParser {
-    fn parse(&self, text_body: &str, html_body: &str, alarm: &mut Alarm, config: MailConfig) -> Result<String, String>;
-}
\ No newline at end of file
+    /// Fills `alarm` from the mail bodies. `Err` means the mail could not be
+    /// parsed at all, problems with single fields go into the `ParseReport`.
+    fn parse(&self, text_body: &str, html_body: &str, alarm: &mut Alarm, config: MailConfig) -> Result<ParseReport, String>;
+}
+
+/// All values accepted as `mail_schema`.
+pub const MAIL_SCHEMAS: &[&str] = &["SL-securCAD", "Plaintext", "Table", "Cobra", "ELDIS", "FireBoard"];
+
+/// Parser registry, unknown schema names and broken field patterns of
+/// `config` are a config error.
+pub fn parser_for(mail_schema: &str, config: &MailConfig) -> Result<Box<dyn MailParser>, String> {
+    match mail_schema {
+        "SL-securCAD" => Ok(Box::new(SecurCadParser)),
+        "Plaintext" => Ok(Box::new(PlaintextParser::new(config)?)),
+        "Table" => Ok(Box::new(TableParser::new(config)?)),
+        "Cobra" => Ok(Box::new(PresetParser::new(&COBRA, config)?)),
+        "ELDIS" => Ok(Box::new(PresetParser::new(&ELDIS, config)?)),
+        "FireBoard" => Ok(Box::new(PresetParser::new(&FIREBOARD, config)?)),
+        _ => Err(format!(
+            "Unknown mail_schema '{}', available: {}",
+            mail_schema,
+            MAIL_SCHEMAS.join(", ")
+        )),
+    }
+}
+
+/// Charset for table content that is not valid UTF-8, `MailConfig::charset`
+/// or Windows-1252 (a superset of ISO-8859-1).
+pub fn table_charset(label: Option<&str>) -> Result<&'static Encoding, String> {
+    match label {
+        Some(label) => Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| format!("Unknown charset '{}'", label)),
+        None => Ok(WINDOWS_1252),
+    }
+}
@@ -1,17 +1,275 @@
-use crate::alarm::Alarm;
-use crate::config::alarm_sources::MailConfig;
+use crate::alarm::{Alarm, Coordinates, ParseReport};
+use crate::config::alarm_sources::{MailConfig, PlaintextFields};
+use crate::coordinates::{self, parse_decimal};
 use crate::mail_parser::MailParser;
 use log::debug;
+use regex::Regex;
+use scraper::Html;
 
-pub struct PlaintextParser;
+/// `Key: value` lines mapped by `plaintext_fields`.
+pub struct PlaintextParser {
+    fields: FieldPatterns,
+}
+
+impl PlaintextParser {
+    pub fn new(config: &MailConfig) -> Result<Self, String> {
+        Ok(Self { fields: FieldPatterns::new(&config.plaintext_fields)? })
+    }
+}
+
+/// One entry of `plaintext_fields`, see `PlaintextFields`.
+enum FieldPattern {
+    LineKey(String),
+    Regex(Regex),
+}
+
+impl FieldPattern {
+    fn new(pattern: &str) -> Result<Self, String> {
+        match pattern.strip_prefix("regex:") {
+            Some(expression) => Regex::new(&format!("(?m){}", expression))
+                .map(FieldPattern::Regex)
+                .map_err(|e| format!("Invalid regex '{}': {}", expression, e)),
+            None => Ok(FieldPattern::LineKey(pattern.to_string())),
+        }
+    }
+
+    /// All values found in `body`, in order of appearance.
+    fn find_all(&self, body: &str) -> Vec<String> {
+        match self {
+            FieldPattern::LineKey(key) => body
+                .lines()
+                .filter_map(|line| line.trim().strip_prefix(key.as_str()))
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+                .collect(),
+            FieldPattern::Regex(regex) => regex
+                .captures_iter(body)
+                .filter_map(|captures| {
+                    captures
+                        .name("value")
+                        .or_else(|| captures.get(1))
+                        .or_else(|| captures.get(0))
+                        .map(|m| m.as_str().trim().to_string())
+                })
+                .filter(|value| !value.is_empty())
+                .collect(),
+        }
+    }
+
+    fn find(&self, body: &str) -> Option<String> {
+        self.find_all(body).into_iter().next()
+    }
+
+    /// `None` if the field is not in `body`, otherwise the parsed coordinates.
+    fn find_coords(&self, body: &str) -> Option<Result<Coordinates, String>> {
+        if let FieldPattern::Regex(regex) = self {
+            if let Some(captures) = regex.captures(body) {
+                if let (Some(lat), Some(lon)) = (captures.name("lat"), captures.name("lon")) {
+                    return Some(match (parse_decimal(lat.as_str()), parse_decimal(lon.as_str())) {
+                        (Some(lat), Some(lon)) => coordinates::validate(lat, lon),
+                        _ => Err(format!("Unparseable coordinates: '{}' '{}'", lat.as_str(), lon.as_str())),
+                    });
+                }
+            }
+        }
+
+        // "lat, lon" in decimal degrees, UTM, MGRS or Gauss-Krüger
+        let value = self.find(body)?;
+        Some(coordinates::parse(&value).map_err(|e| format!("Unparseable coordinates '{}': {}", value, e)))
+    }
+}
+
+/// The set fields of a `PlaintextFields` mapping, compiled when the parser is
+/// built so a broken regex fails the config load instead of every mail.
+pub struct FieldPatterns(Vec<(&'static str, FieldPattern)>);
+
+impl FieldPatterns {
+    pub fn new(fields: &PlaintextFields) -> Result<Self, String> {
+        let patterns = [
+            ("id", &fields.id), ("title", &fields.title), ("stichwort", &fields.stichwort), ("text", &fields.text),
+            ("street", &fields.street), ("city", &fields.city), ("object", &fields.object), ("object_id", &fields.object_id),
+            ("info", &fields.info), ("utm", &fields.utm), ("coords", &fields.coords), ("units", &fields.units),
+        ];
+        let mut compiled = vec![];
+        for (name, pattern) in patterns {
+            if let Some(pattern) = pattern {
+                compiled.push((name, FieldPattern::new(pattern).map_err(|e| format!("plaintext_fields.{}: {}", name, e))?));
+            }
+        }
+        Ok(Self(compiled))
+    }
+
+    fn get(&self, name: &str) -> Option<&FieldPattern> {
+        self.0.iter().find(|(field, _)| *field == name).map(|(_, pattern)| pattern)
+    }
+}
+
+/// Text content of an html body, used when a mail has no text/plain part.
+fn html_to_text(html_body: &str) -> String {
+    let document = Html::parse_document(html_body);
+    document
+        .root_element()
+        .text()
+        .map(|t| t.trim())
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The text body, or the text content of the html body if there is no text part.
+pub fn body_text(text_body: &str, html_body: &str) -> String {
+    if text_body.trim().is_empty() && !html_body.trim().is_empty() {
+        html_to_text(html_body)
+    } else {
+        text_body.to_string()
+    }
+}
 
 impl MailParser for PlaintextParser {
-    fn parse(&self, text_body: &str, html_body: &str, alarm: &mut Alarm, _config: MailConfig) -> Result<String, String> {
+    fn parse(&self, text_body: &str, html_body: &str, alarm: &mut Alarm, config: MailConfig) -> Result<ParseReport, String> {
         debug!("text: {}", text_body);
         debug!("html: {}", html_body);
 
-        alarm.add_to_text(text_body.parse().unwrap());
+        let body = body_text(text_body, html_body);
+        let mut report = ParseReport::default();
+
+        // without field mappings the whole body is the alarm text
+        if config.plaintext_fields.is_empty() {
+            report.field("text", !body.trim().is_empty());
+            alarm.add_to_text(body);
+            return Ok(report);
+        }
+
+        parse_fields(&body, &self.fields, alarm, &config, &mut report);
+
+        Ok(report)
+    }
+}
+
+/// Fills the alarm from `body` according to `fields`, shared by all line based parsers.
+/// Every mapped field is recorded as found or missing in `report`.
+pub fn parse_fields(body: &str, fields: &FieldPatterns, alarm: &mut Alarm, config: &MailConfig, report: &mut ParseReport) {
+    // value of a mapped field, recorded in the report
+    let mut find = |name: &str| -> Option<String> {
+        let value = fields.get(name)?.find(body);
+        report.field(name, value.is_some());
+        value
+    };
+
+    if let Some(id) = find("id") {
+        alarm.set_id(id);
+    }
+
+    let stichwort = find("stichwort")
+        .map(|stichwort| match config.stichwoerter.get(&stichwort.to_uppercase()) {
+            Some(mapped) => mapped.clone(),
+            None => stichwort,
+        });
+    if let Some(stichwort) = &stichwort {
+        alarm.set_stichwort(stichwort.clone());
+    }
+
+    match find("title") {
+        Some(title) => alarm.set_title(title),
+        None => {
+            if let Some(stichwort) = stichwort {
+                alarm.set_title(stichwort);
+            }
+        }
+    }
+
+    if let Some(street) = find("street") {
+        alarm.address.set_street(street);
+    }
+    if let Some(city) = find("city") {
+        alarm.address.set_city(city);
+    }
+    if let Some(object) = find("object") {
+        alarm.address.set_object(object);
+    }
+    if let Some(object_id) = find("object_id") {
+        alarm.address.set_object_id(object_id);
+    }
+    if let Some(info) = find("info") {
+        alarm.address.set_info(info);
+    }
+    if let Some(utm) = find("utm") {
+        alarm.address.set_utm(utm);
+    }
+
+    match fields.get("text") {
+        Some(pattern) => {
+            let text = pattern.find_all(body);
+            report.field("text", !text.is_empty());
+            alarm.set_text(text.join("\n"));
+        }
+        None => alarm.add_to_text(body.to_string()),
+    }
+
+    if let Some(pattern) = fields.get("coords") {
+        match pattern.find_coords(body) {
+            Some(Ok(coords)) => {
+                report.field("coords", true);
+                alarm.address.set_coords(coords);
+            }
+            Some(Err(e)) => {
+                report.field("coords", false);
+                report.warn(e);
+            }
+            None => report.field("coords", false),
+        }
+    }
+
+    if let Some(pattern) = fields.get("units") {
+        let ignore_patterns: Vec<Regex> = config.ignore_units
+            .iter()
+            .filter_map(|s| Regex::new(s).ok())
+            .collect();
+
+        let values = pattern.find_all(body);
+        report.field("units", !values.is_empty());
+
+        for value in values {
+            for unit in value.split(',').map(|u| u.trim()).filter(|u| !u.is_empty()) {
+                if ignore_patterns.iter().any(|re| re.is_match(unit)) {
+                    debug!("ignoring unit: {}", unit);
+                    continue;
+                }
+
+                alarm.add_unit(unit.to_string());
+                if let Some(template_name) = config.alarm_template_keywords.get(unit) {
+                    alarm.add_template_name(template_name.clone());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn invalid_field_pattern_is_a_config_error() {
+        let fields: PlaintextFields = serde_json::from_value(serde_json::json!({
+            "stichwort": "Stichwort:",
+            "city": "regex:^Ort: (?P<value>.+)$",
+        }))
+        .unwrap();
+        assert!(FieldPatterns::new(&fields).is_ok());
+
+        let fields: PlaintextFields = serde_json::from_value(serde_json::json!({ "units": "regex:^Einsatzmittel: (?P<value>.+$" })).unwrap();
+        let error = FieldPatterns::new(&fields).err().unwrap();
+        assert!(error.starts_with("plaintext_fields.units: Invalid regex"), "{}", error);
+    }
 
-        Ok(format!("Plaintext Parser done: {}", text_body))
+    #[test]
+    fn coords_in_decimal_or_whole_degrees() {
+        let pattern = FieldPattern::new("Koordinaten:").unwrap();
+        let coords = |body: &str| pattern.find_coords(body).unwrap().map(|coords| (coords.lat, coords.lon));
+        assert_eq!(coords("Koordinaten: 50,12345 / 8,6789"), Ok((Some(50.12345), Some(8.6789))));
+        assert_eq!(coords("Koordinaten: 50, 8"), Ok((Some(50.0), Some(8.0))));
+        assert!(coords("Koordinaten: Hauptstraße").is_err());
+        assert!(pattern.find_coords("Ort: Musterstadt").is_none());
     }
 }
@@ -0,0 +1,210 @@
+use crate::alarm::{Alarm, ParseReport};
+use crate::config::alarm_sources::{MailConfig, PlaintextFields};
+use crate::mail_parser::plaintext_parser::{body_text, parse_fields, FieldPatterns};
+use crate::mail_parser::MailParser;
+
+/// Line keys of a Leitstellen format, any key of a field may be used (the
+/// first match in the mail wins). Empty means the format has no such field.
+pub struct KeyTable {
+    id: &'static [&'static str],
+    title: &'static [&'static str],
+    stichwort: &'static [&'static str],
+    text: &'static [&'static str],
+    street: &'static [&'static str],
+    city: &'static [&'static str],
+    /// `Key: street, city` in one line, used if `street` and `city` are empty
+    address: &'static [&'static str],
+    object: &'static [&'static str],
+    object_id: &'static [&'static str],
+    info: &'static [&'static str],
+    utm: &'static [&'static str],
+    coords: &'static [&'static str],
+    units: &'static [&'static str],
+}
+
+/// Cobra: one `Key: value` line per field, units comma separated in `Einsatzmittel:` lines.
+pub const COBRA: KeyTable = KeyTable {
+    id: &["Einsatznummer", "Einsatz-Nr."],
+    title: &["Meldebild"],
+    stichwort: &["Stichwort", "Einsatzstichwort"],
+    text: &["Bemerkung", "Hinweis"],
+    street: &["Straße", "Strasse"],
+    city: &["Ort"],
+    address: &[],
+    object: &["Objekt"],
+    object_id: &["Objektnummer", "Objekt-Nr."],
+    info: &["Ortszusatz"],
+    utm: &["UTM"],
+    coords: &["Koordinaten", "Koordinate"],
+    units: &["Einsatzmittel", "Alarmierte Einsatzmittel"],
+};
+
+/// ELDIS: upper case keys padded before the colon (`EINSATZSTICHWORT : B2`),
+/// one `EINSATZMITTEL` line per unit.
+pub const ELDIS: KeyTable = KeyTable {
+    id: &["EINSATZNUMMER", "EINSATZNR"],
+    title: &["MELDEBILD"],
+    stichwort: &["EINSATZSTICHWORT", "STICHWORT"],
+    text: &["SACHVERHALT", "BEMERKUNG"],
+    street: &["STRASSE"],
+    city: &["ORT"],
+    address: &[],
+    object: &["OBJEKT"],
+    object_id: &["OBJEKTNUMMER"],
+    info: &["ORTSZUSATZ", "ZUSATZ"],
+    utm: &["UTM"],
+    coords: &["KOORDINATEN", "WGS84"],
+    units: &["EINSATZMITTEL", "EM"],
+};
+
+/// FireBoard: `Key: value` lines with street and city in one
+/// `Adresse: Hauptstraße 5, 12345 Musterstadt` line.
+pub const FIREBOARD: KeyTable = KeyTable {
+    id: &["Einsatznummer", "Einsatz-ID"],
+    title: &["Meldebild"],
+    stichwort: &["Einsatzstichwort", "Stichwort"],
+    text: &["Sachverhalt", "Bemerkung"],
+    street: &[],
+    city: &[],
+    address: &["Adresse"],
+    object: &["Objekt"],
+    object_id: &[],
+    info: &["Einsatzort-Info", "Ortsinfo"],
+    utm: &["UTM"],
+    coords: &["Koordinaten", "Position"],
+    units: &["Einheiten", "Fahrzeuge"],
+};
+
+/// Case-insensitive `regex:` pattern for lines like `KEY: value` or `KEY : value`.
+fn key_pattern(keys: &[&str], value: &str) -> Option<String> {
+    if keys.is_empty() {
+        return None;
+    }
+    let keys = keys.iter().map(|key| regex::escape(key)).collect::<Vec<_>>().join("|");
+    Some(format!(r"regex:(?i)^[ \t]*(?:{})[ \t]*:[ \t]*{}", keys, value))
+}
+
+impl KeyTable {
+    /// The table as `plaintext_fields` mapping.
+    pub fn fields(&self) -> PlaintextFields {
+        let line = |keys: &[&str]| key_pattern(keys, r"(?P<value>.*?)[ \t]*$");
+        PlaintextFields {
+            id: line(self.id),
+            title: line(self.title),
+            stichwort: line(self.stichwort),
+            text: line(self.text),
+            street: line(self.street).or_else(|| key_pattern(self.address, r"(?P<value>[^,\r\n]+)")),
+            city: line(self.city).or_else(|| key_pattern(self.address, r"[^,\r\n]*,[ \t]*(?P<value>[^\r\n]+)")),
+            object: line(self.object),
+            object_id: line(self.object_id),
+            info: line(self.info),
+            utm: line(self.utm),
+            coords: line(self.coords),
+            units: line(self.units),
+        }
+    }
+}
+
+/// Line based mails of a Leitstellen system, parsed like `Plaintext` with the
+/// key table of the format. Every field set in `plaintext_fields` replaces
+/// the preset for that field.
+pub struct PresetParser {
+    fields: FieldPatterns,
+}
+
+impl PresetParser {
+    pub fn new(table: &KeyTable, config: &MailConfig) -> Result<Self, String> {
+        Ok(Self { fields: FieldPatterns::new(&table.fields().merged_with(&config.plaintext_fields))? })
+    }
+}
+
+impl MailParser for PresetParser {
+    fn parse(&self, text_body: &str, html_body: &str, alarm: &mut Alarm, config: MailConfig) -> Result<ParseReport, String> {
+        let mut report = ParseReport::default();
+        parse_fields(&body_text(text_body, html_body), &self.fields, alarm, &config, &mut report);
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(mail_schema: &str, plaintext_fields: serde_json::Value) -> MailConfig {
+        serde_json::from_value(serde_json::json!({
+            "name": "test", "active": true, "host": "", "port": 993, "tls": true, "max_age": 0,
+            "alarm_sender": "", "alarm_subject": "", "alarm_template_keywords": {}, "mail_schema": mail_schema,
+            "stichwoerter": {}, "ignore_units": [], "polling": false, "polling_interval": 0, "idle": false,
+            "plaintext_fields": plaintext_fields,
+        }))
+        .unwrap()
+    }
+
+    /// Parses the text body of `fixtures/mail/<name>`.
+    fn parse(name: &str, table: &'static KeyTable, config: MailConfig) -> (Alarm, ParseReport) {
+        let raw = std::fs::read(format!("{}/fixtures/mail/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap();
+        let body = mailparse::parse_mail(&raw).unwrap().get_body().unwrap();
+        let mut alarm = Alarm::new();
+        let report = PresetParser::new(table, &config).unwrap().parse(&body, "", &mut alarm, config).unwrap();
+        (alarm, report)
+    }
+
+    #[test]
+    fn cobra_mail() {
+        let (alarm, report) = parse("cobra.eml", &COBRA, config("Cobra", serde_json::json!({})));
+        assert_eq!(alarm.id, "2026-004711");
+        assert_eq!(alarm.stichwort, "B3");
+        assert_eq!(alarm.title, "Brand Wohnhaus, Personen vermisst");
+        assert_eq!(alarm.text, "Rauchentwicklung aus Fenster");
+        assert_eq!(alarm.address.street, "Hauptstraße 5");
+        assert_eq!(alarm.address.city, "12345 Musterstadt");
+        assert_eq!(alarm.address.info, "Hinterhaus, 2. OG");
+        assert_eq!(alarm.address.object, "Seniorenheim Sonnenhof");
+        assert_eq!(alarm.address.object_id, "OBJ-0815");
+        assert_eq!((alarm.address.coords.lat, alarm.address.coords.lon), (Some(50.12345), Some(8.6789)));
+        assert_eq!(alarm.units, vec!["FL MST 1/46-1", "FL MST 1/33-1", "RTW 1/83-2"]);
+        assert!(report.missing.contains(&"utm".to_string()));
+    }
+
+    #[test]
+    fn eldis_mail() {
+        let (alarm, report) = parse("eldis.eml", &ELDIS, config("ELDIS", serde_json::json!({})));
+        assert_eq!(alarm.id, "26-113-0042");
+        assert_eq!(alarm.stichwort, "TH 1");
+        assert_eq!(alarm.title, "Baum auf Straße");
+        assert_eq!(alarm.text, "Baum blockiert Fahrbahn\nAnrufer wartet vor Ort");
+        assert_eq!(alarm.address.street, "Waldweg 12");
+        assert_eq!(alarm.address.city, "12345 Musterstadt-Süd");
+        assert_eq!(alarm.address.info, "Höhe Wanderparkplatz");
+        assert_eq!((alarm.address.coords.lat, alarm.address.coords.lon), (Some(50.2001), Some(8.5002)));
+        assert_eq!(alarm.units, vec!["FL MST 2/44-1", "FL MST 2/11-1"]);
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn fireboard_mail() {
+        let (alarm, _) = parse("fireboard.eml", &FIREBOARD, config("FireBoard", serde_json::json!({})));
+        assert_eq!(alarm.id, "FB-98765");
+        assert_eq!(alarm.stichwort, "RD 2");
+        assert_eq!(alarm.title, "Person gestürzt");
+        assert_eq!(alarm.text, "Person nicht ansprechbar");
+        assert_eq!(alarm.address.street, "Bahnhofstraße 1");
+        assert_eq!(alarm.address.city, "12345 Musterstadt");
+        assert_eq!(alarm.address.info, "Bahnsteig 3");
+        assert_eq!((alarm.address.coords.lat, alarm.address.coords.lon), (Some(50.11), Some(8.682)));
+        assert_eq!(alarm.units, vec!["FL MST 1/83-1", "FL MST 1/10"]);
+    }
+
+    #[test]
+    fn plaintext_fields_replace_the_preset() {
+        let fields = serde_json::json!({ "title": "Stichwort:", "city": "regex:(?m)^Ort: \\d{5} (?P<value>.+)$" });
+        let (alarm, _) = parse("cobra.eml", &COBRA, config("Cobra", fields));
+        assert_eq!(alarm.title, "B3");
+        assert_eq!(alarm.address.city, "Musterstadt");
+        assert_eq!(alarm.address.street, "Hauptstraße 5");
+
+        let broken = config("Cobra", serde_json::json!({ "units": "regex:^Einsatzmittel: (?P<value>.+$" }));
+        assert!(PresetParser::new(&COBRA, &broken).is_err());
+    }
+}
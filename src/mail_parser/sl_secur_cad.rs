@@ -1,23 +1,36 @@
-use crate::alarm::{Alarm, Coordinates};
+use crate::alarm::{Alarm, Coordinates, ParseReport};
+use crate::coordinates;
 use crate::config::alarm_sources::MailConfig;
 use crate::mail_parser::helpers::{get_table_key_order, parse_tables};
-use crate::mail_parser::MailParser;
+use crate::mail_parser::{table_charset, MailParser};
 use log::{debug, info};
 use regex::Regex;
 
 pub struct SecurCadParser;
 
 impl MailParser for SecurCadParser {
-    fn parse(&self, text_body: &str, html_body: &str, alarm: &mut Alarm, config: MailConfig) -> Result<String, String> {
+    fn parse(&self, text_body: &str, html_body: &str, alarm: &mut Alarm, config: MailConfig) -> Result<ParseReport, String> {
         let body = text_body.to_owned() + html_body;
 
-        let table = parse_tables(&*body);
+        let charset = table_charset(config.charset.as_deref())?;
+
+        let mut report = ParseReport::default();
+        let table = parse_tables(&body, charset, &mut report);
         for (key, value) in table.iter() {
             debug!("{}: {:?}", key, value);
         }
 
         let stichwoerter = config.stichwoerter.clone();
 
+        report.field("id", table.contains_key("Auftragsnummer:"));
+        report.field("stichwort", table.contains_key("Einsatzstichwort:"));
+        report.field("title", table.contains_key("Notfallgeschehen:"));
+        report.field("text", table.contains_key("Sachverhalt:") || table.contains_key("Objekt:"));
+        report.field("street", table.contains_key("Strasse:") || table.contains_key("Strasse / Hs.-Nr.:"));
+        report.field("city", table.contains_key("PLZ / Ort:"));
+        report.field("info", table.contains_key("Info:"));
+        report.field("utm", table.contains_key("UTM - Koordinaten:"));
+
         // Einsatznummer - ID
         let id = match table.get("Auftragsnummer:") {
             Some(id) => id[0].clone(),
@@ -36,6 +49,7 @@ impl MailParser for SecurCadParser {
             Some(stichwort) => stichwort.clone(),
             None => stichwort
         };
+        alarm.set_stichwort(stichwort.clone());
 
         let notfallgeschehen = match table.get("Notfallgeschehen:") {
             Some(notfallgeschehen) => notfallgeschehen[0].clone(),
@@ -145,52 +159,45 @@ impl MailParser for SecurCadParser {
             None => vec![]
         };
 
-        println!("koords: {:?}", lat_lon);
-
-        let lat_lon_reg = regex::Regex::new(r"(\d+,\d+)").unwrap();
-        // parsing latitude - geogr. Breite
-        let lat = match lat_lon.get(1) {
-            Some(lat_str) => match lat_lon_reg.captures(lat_str) {
-                Some(lat) => {
-                    if let Some(lat_val) = lat.get(1) {
-                        let lat_temp = lat_val.as_str().replace(",", "."); // replace ',' with '.'
-                        lat_temp.parse::<f64>().ok() // parse as f64
-                    } else {
-                        None
-                    }
-                },
-                None => None,
-            },
-            None => None,
-        };
+        debug!("koords: {:?}", lat_lon);
 
-        // parsing longitude - geogr. Länge
-        let lon = match lat_lon.get(0) {
-            Some(lon_str) => match lat_lon_reg.captures(lon_str) {
-                Some(lon) => {
-                    if let Some(lon_val) = lon.get(1) {
-                        let lon_temp = lon_val.as_str().replace(",", "."); // replace , with .
-                        lon_temp.parse::<f64>().ok()
-                    } else {
-                        None
-                    }
-                },
-                None => None,
-            },
-            None => None,
+        let lat_lon_reg = regex::Regex::new(r"(-?\d+[.,]\d+)").unwrap();
+        let parse_value = |value: Option<&String>| {
+            value
+                .and_then(|value| lat_lon_reg.captures(value))
+                .and_then(|captures| captures.get(1))
+                .and_then(|value| coordinates::parse_decimal(value.as_str()))
         };
+        // geogr. Breite / geogr. Länge
+        let mut lat = parse_value(lat_lon.get(1));
+        let mut lon = parse_value(lat_lon.get(0));
+
+        if let (Some(lat_val), Some(lon_val)) = (lat, lon) {
+            if let Err(e) = coordinates::validate(lat_val, lon_val) {
+                report.warn(format!("Invalid coordinates {:?}: {}", lat_lon, e));
+                lat = None;
+                lon = None;
+            }
+        } else if !lat_lon.is_empty() {
+            report.warn(format!("Unparseable coordinates: {:?}", lat_lon));
+        }
 
+        report.field("coords", lat.is_some() && lon.is_some());
 
         alarm.address.set_coords(Coordinates { lat, lon });
 
         // Units
-        let key_order = get_table_key_order(&*body);
+        let key_order = get_table_key_order(&body, charset);
         let unit_start_index = if let Some(index) = key_order.iter().position(|x| x == "Ressourcen") {
             index + 1
         } else {
             0
         };
 
+        if !key_order.iter().any(|x| x == "Ressourcen") {
+            report.warn("Unit section 'Ressourcen' not found".to_string());
+        }
+
         let mut unit_end_index = 0;
 
         unit_end_index = if let Some(index) = key_order.iter().position(|x| x == "Meldender des Hilfeersuchens") {
@@ -245,7 +252,6 @@ impl MailParser for SecurCadParser {
 
         info!("{:?}", alarm);
 
-        //
-        Ok(format!("Parsed SecurCad: {}", text_body))
+        Ok(report)
     }
 }
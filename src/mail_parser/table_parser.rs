@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use crate::alarm::{Alarm, ParseReport};
+use crate::config::alarm_sources::{CoordsOrder, MailConfig};
+use crate::coordinates;
+use crate::mail_parser::helpers::{get_table_key_order, parse_tables};
+use crate::mail_parser::{table_charset, MailParser};
+use log::debug;
+use regex::Regex;
+
+static NUMBER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"-?\d+[.,]\d+").unwrap());
+
+/// Generic parser for html table alarm mails, driven by `MailConfig::table_schema`.
+pub struct TableParser {
+    title_pattern: Option<Regex>,
+}
+
+impl TableParser {
+    pub fn new(config: &MailConfig) -> Result<Self, String> {
+        let title_pattern = match &config.table_schema.title_pattern {
+            Some(pattern) => Some(Regex::new(pattern).map_err(|e| format!("Invalid title_pattern '{}': {}", pattern, e))?),
+            None => None,
+        };
+        Ok(Self { title_pattern })
+    }
+}
+
+/// First cell of the first key in `keys` that is present and not empty.
+fn first_value(table: &HashMap<String, Vec<String>>, keys: &[String]) -> Option<String> {
+    keys.iter()
+        .filter_map(|key| table.get(key))
+        .filter_map(|values| values.first())
+        .map(|value| value.trim().to_string())
+        .find(|value| !value.is_empty())
+}
+
+impl MailParser for TableParser {
+    fn parse(&self, text_body: &str, html_body: &str, alarm: &mut Alarm, config: MailConfig) -> Result<ParseReport, String> {
+        let body = text_body.to_owned() + html_body;
+        let schema = &config.table_schema;
+
+        let charset = table_charset(config.charset.as_deref())?;
+
+        let mut report = ParseReport::default();
+        let table = parse_tables(&body, charset, &mut report);
+        for (key, value) in table.iter() {
+            debug!("{}: {:?}", key, value);
+        }
+
+        // value of a schema field, recorded in the report
+        let mut find = |name: &str, keys: &[String]| {
+            if keys.is_empty() {
+                return None;
+            }
+            let value = first_value(&table, keys);
+            report.field(name, value.is_some());
+            value
+        };
+
+        if let Some(id) = find("id", &schema.id) {
+            alarm.set_id(id);
+        }
+
+        let stichwort = find("stichwort", &schema.stichwort).map(|stichwort| {
+            match config.stichwoerter.get(&stichwort.to_uppercase()) {
+                Some(mapped) => mapped.clone(),
+                None => stichwort,
+            }
+        });
+        if let Some(stichwort) = &stichwort {
+            alarm.set_stichwort(stichwort.clone());
+        }
+
+        match find("title", &schema.title) {
+            Some(title) => {
+                let title = self
+                    .title_pattern
+                    .as_ref()
+                    .and_then(|pattern| pattern.captures(&title))
+                    .and_then(|captures| captures.get(1))
+                    .map(|m| m.as_str().to_string())
+                    .unwrap_or(title);
+                alarm.set_title(title);
+            }
+            None => {
+                if let Some(stichwort) = stichwort {
+                    alarm.set_title(stichwort);
+                }
+            }
+        }
+
+        if let Some(text) = find("text", &schema.text) {
+            alarm.set_text(text);
+        }
+        if let Some(street) = find("street", &schema.street) {
+            alarm.address.set_street(street);
+        }
+        if let Some(city) = find("city", &schema.city) {
+            alarm.address.set_city(city);
+        }
+        if let Some(object) = find("object", &schema.object) {
+            alarm.address.set_object(object);
+        }
+        if let Some(object_id) = find("object_id", &schema.object_id) {
+            alarm.address.set_object_id(object_id);
+        }
+        if let Some(info) = find("info", &schema.info) {
+            alarm.address.set_info(info);
+        }
+        if let Some(utm) = find("utm", &schema.utm) {
+            alarm.address.set_utm(utm);
+        }
+
+        // both numbers may be in one cell or in one cell each
+        if let Some(cells) = schema.coords.iter().find_map(|key| table.get(key)) {
+            let numbers: Vec<f64> = cells
+                .iter()
+                .flat_map(|cell| NUMBER.find_iter(cell).map(|m| m.as_str().replace(',', ".")).collect::<Vec<_>>())
+                .filter_map(|number| number.parse::<f64>().ok())
+                .collect();
+
+            // otherwise UTM, MGRS or Gauss-Krüger in one cell
+            let coords = match numbers.as_slice() {
+                [first, second, ..] => match schema.coords_order {
+                    CoordsOrder::LatLon => coordinates::validate(*first, *second),
+                    CoordsOrder::LonLat => coordinates::validate(*second, *first),
+                },
+                _ => coordinates::parse(&cells.join(" ")),
+            };
+
+            match coords {
+                Ok(coords) => {
+                    alarm.address.set_coords(coords);
+                    report.field("coords", true);
+                }
+                Err(e) => {
+                    report.field("coords", false);
+                    report.warn(format!("Unparseable coordinates {:?}: {}", cells, e));
+                }
+            }
+        } else if !schema.coords.is_empty() {
+            report.field("coords", false);
+        }
+
+        // Units: every row key between units_start and the next units_end key in the mail
+        if let Some(units_start) = &schema.units_start {
+            let key_order = get_table_key_order(&body, charset);
+            let start = key_order.iter().position(|key| key == units_start).map(|idx| idx + 1);
+            let end = start
+                .and_then(|start| {
+                    key_order[start..]
+                        .iter()
+                        .position(|key| schema.units_end.contains(key))
+                        .map(|idx| start + idx)
+                })
+                .unwrap_or(key_order.len());
+
+            report.field("units", start.is_some());
+            if start.is_none() {
+                report.warn(format!("Unit section '{}' not found", units_start));
+            }
+
+            if let Some(start) = start.filter(|start| *start < end) {
+                let ignore_patterns: Vec<Regex> = config.ignore_units
+                    .iter()
+                    .filter_map(|s| Regex::new(s).ok())
+                    .collect();
+
+                for key in &key_order[start..end] {
+                    if !table.contains_key(key) {
+                        continue;
+                    }
+                    if ignore_patterns.iter().any(|re| re.is_match(key)) {
+                        debug!("ignoring unit: {}", key);
+                        continue;
+                    }
+
+                    alarm.add_unit(key.clone());
+                    if let Some(template_name) = config.alarm_template_keywords.get(key) {
+                        alarm.add_template_name(template_name.clone());
+                    }
+                }
+            }
+        }
+
+        debug!("{:?}", alarm);
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(table_schema: serde_json::Value) -> MailConfig {
+        serde_json::from_value(serde_json::json!({
+            "name": "test", "active": true, "host": "", "port": 993, "tls": true, "max_age": 0,
+            "alarm_sender": "", "alarm_subject": "", "alarm_template_keywords": {}, "mail_schema": "Table",
+            "stichwoerter": {}, "ignore_units": [], "polling": false, "polling_interval": 0, "idle": false,
+            "table_schema": table_schema,
+        }))
+        .unwrap()
+    }
+
+    fn rows(rows: &[(&str, &str)]) -> String {
+        let rows: String = rows.iter().map(|(key, value)| format!("<tr><td>{}</td><td>{}</td></tr>", key, value)).collect();
+        format!("<html><body><table>{}</table></body></html>", rows)
+    }
+
+    fn parse(html: &str, schema: serde_json::Value) -> Alarm {
+        let mut alarm = Alarm::new();
+        TableParser::new(&config(schema.clone())).unwrap().parse("", html, &mut alarm, config(schema)).unwrap();
+        alarm
+    }
+
+    #[test]
+    fn title_pattern() {
+        let html = rows(&[("Einsatzstichwort:", "B3 (Brand Wohnhaus)")]);
+        let alarm = parse(&html, serde_json::json!({ "title": ["Einsatzstichwort:"], "title_pattern": r"\((.*)\)" }));
+        assert_eq!(alarm.title, "Brand Wohnhaus");
+
+        let broken = config(serde_json::json!({ "title": ["Einsatzstichwort:"], "title_pattern": r"\((.*\)" }));
+        let error = TableParser::new(&broken).err().unwrap();
+        assert!(error.starts_with("Invalid title_pattern"), "{}", error);
+    }
+
+    #[test]
+    fn units_end_at_the_first_marker_in_the_mail() {
+        let html = rows(&[
+            ("Einsatznummer:", "4711"),
+            ("Ressourcen", ""),
+            ("FL MST 1/46-1", "alarmiert"),
+            ("FL MST 1/33-1", "alarmiert"),
+            ("Meldender:", "Max Mustermann"),
+            ("RTW 1/83-2", "alarmiert"),
+            ("Druckdatum:", "19.10.2026"),
+        ]);
+        // the later marker is listed first in the config
+        let schema = serde_json::json!({ "id": ["Einsatznummer:"], "units_start": "Ressourcen", "units_end": ["Druckdatum:", "Meldender:"] });
+        let alarm = parse(&html, schema);
+        assert_eq!(alarm.id, "4711");
+        assert_eq!(alarm.units, vec!["FL MST 1/46-1", "FL MST 1/33-1"]);
+    }
+
+    #[test]
+    fn units_end_before_units_start_is_ignored() {
+        let html = rows(&[("Druckdatum:", "19.10.2026"), ("Ressourcen", ""), ("FL MST 1/46-1", ""), ("RTW 1/83-2", "")]);
+        let schema = serde_json::json!({ "units_start": "Ressourcen", "units_end": ["Druckdatum:"] });
+        let alarm = parse(&html, schema);
+        assert_eq!(alarm.units, vec!["FL MST 1/46-1", "RTW 1/83-2"]);
+    }
+
+    #[test]
+    fn coords_in_one_or_two_cells() {
+        let schema = serde_json::json!({ "coords": ["Koordinaten:"], "coords_order": "LonLat" });
+        let alarm = parse(&rows(&[("Koordinaten:", "8,6789 / 50,12345")]), schema);
+        assert_eq!((alarm.address.coords.lat, alarm.address.coords.lon), (Some(50.12345), Some(8.6789)));
+
+        let html = "<table><tr><td>Koordinaten:</td><td>50.12345</td><td>8.6789</td></tr></table>";
+        let alarm = parse(html, serde_json::json!({ "coords": ["Koordinaten:"] }));
+        assert_eq!((alarm.address.coords.lat, alarm.address.coords.lon), (Some(50.12345), Some(8.6789)));
+    }
+}
@@ -10,10 +10,26 @@ use colored::Colorize;
 mod config;
 mod alarm_handler;
 mod alarm;
+mod coordinates;
+mod geocoder;
+mod enrichment;
+mod routing;
+mod stichwort;
 mod mail_handler;
+mod mail_filter;
+mod pop3_client;
+mod smtp_server;
+mod replay;
+mod archive;
 mod mail_parser;
 mod apis;
 mod serial_handler;
+mod frame_reader;
+mod stream_port;
+mod source_status;
+mod dme_decoder;
+mod pager_text;
+mod vehicle_status;
 
 struct RotatingFileWriter {
     path: PathBuf,
@@ -100,7 +116,8 @@ fn rotated_path(base: &Path, idx: usize) -> PathBuf {
     base.with_file_name(format!("{file_name}.{idx}"))
 }
 
-fn setup_logger() -> Result<(), fern::InitError> {
+/// `cli_mode` logs to stderr only, so stdout stays clean for command output.
+fn setup_logger(cli_mode: bool) -> Result<(), fern::InitError> {
     let debug = false;
     let default_level = if debug {
         log::LevelFilter::Debug
@@ -118,7 +135,7 @@ fn setup_logger() -> Result<(), fern::InitError> {
         .filter(|v| *v > 0)
         .unwrap_or(5);
     let log_file_path = std::env::var("LOG_FILE_PATH").unwrap_or_else(|_| "config/app.log".to_string());
-    let log_to_file = std::env::var("LOG_TO_FILE")
+    let log_to_file = !cli_mode && std::env::var("LOG_TO_FILE")
         .map(|v| !matches!(v.as_str(), "0" | "false" | "FALSE" | "False"))
         .unwrap_or(true);
 
@@ -142,8 +159,12 @@ fn setup_logger() -> Result<(), fern::InitError> {
                 level_color,
                 message
             ));
-        })
-        .chain(std::io::stdout());
+        });
+    let stdout_dispatch = if cli_mode {
+        stdout_dispatch.chain(std::io::stderr())
+    } else {
+        stdout_dispatch.chain(std::io::stdout())
+    };
 
     let mut base = fern::Dispatch::new()
         .level(default_level)
@@ -210,10 +231,24 @@ fn log_startup_config(configs: &config::Configs) {
 
         // 2. Print individual details
         for source in active_mail_sources {
-            info!(
-            "Mail Source {} wartet auf Mails von {} mit dem Betreff {}.",
-            source.name, source.alarm_sender, source.alarm_subject
-        );
+            if source.rules.is_empty() {
+                info!(
+                "Mail Source {} wartet auf Mails von {} mit dem Betreff {}.",
+                source.name, source.alarm_sender, source.alarm_subject
+            );
+            } else {
+                for rule in &source.rules {
+                    let senders = if rule.senders.is_empty() {
+                        "*".to_string()
+                    } else {
+                        rule.senders.join(", ")
+                    };
+                    info!(
+                    "Mail Source {} wartet auf Mails von {} mit dem Betreff {}.",
+                    source.name, senders, rule.subject
+                );
+                }
+            }
         }
     }
 
@@ -234,9 +269,15 @@ fn log_startup_config(configs: &config::Configs) {
 
         // 2. Print individual details
         for source in active_serial_sources {
+            let port = match (&source.usb, &source.network, &source.pipe) {
+                (_, _, Some(pipe)) => format!("Pipe {}", pipe),
+                (_, Some(network), None) => format!("{:?} {}", network.mode, network.address),
+                (Some(usb), None, None) => format!("USB {}:{} (baudrate {})", usb.vid, usb.pid, source.baudrate),
+                (None, None, None) => format!("{} (baudrate {})", source.port, source.baudrate),
+            };
             info!(
-            "Serial Source {} wartet auf Daten von {} (baudrate {}).",
-            source.name, source.port, source.baudrate
+            "Serial Source {} wartet auf Daten von {}.",
+            source.name, port
         );
         }
     }
@@ -250,7 +291,9 @@ fn log_startup_config(configs: &config::Configs) {
     }
 }
 
-async fn start_healthcheck_server(port: u16) {
+/// `/` answers `OK` while the server runs, `/status` the source states as
+/// JSON, with status 503 if a source is disconnected.
+async fn start_healthcheck_server(port: u16, status: source_status::SourceStatus, vehicles: vehicle_status::VehicleStatus) {
     let addr = format!("0.0.0.0:{}", port);
     let listener = match tokio::net::TcpListener::bind(&addr).await {
         Ok(l) => l,
@@ -263,8 +306,34 @@ async fn start_healthcheck_server(port: u16) {
 
     loop {
         if let Ok((mut socket, _)) = listener.accept().await {
+            let status = status.clone();
+            let vehicles = vehicles.clone();
             tokio::spawn(async move {
-                let response = "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nOK";
+                let mut request = [0u8; 1024];
+                let read = tokio::io::AsyncReadExt::read(&mut socket, &mut request).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..read]);
+                let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+                let response = if path == "/status" {
+                    let (ok, sources) = status.snapshot();
+                    let body = serde_json::json!({ "ok": ok, "sources": sources }).to_string();
+                    let status_line = if ok { "200 OK" } else { "503 Service Unavailable" };
+                    format!(
+                        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                        status_line,
+                        body.len(),
+                        body
+                    )
+                } else if path == "/vehicles" {
+                    let body = serde_json::json!({ "vehicles": vehicles.snapshot() }).to_string();
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                } else {
+                    "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nOK".to_string()
+                };
                 let _ = tokio::io::AsyncWriteExt::write_all(&mut socket, response.as_bytes()).await;
             });
         }
@@ -273,7 +342,10 @@ async fn start_healthcheck_server(port: u16) {
 
 #[tokio::main]
 async fn main() {
-    if let Err(e) = setup_logger() {
+    let args: Vec<String> = std::env::args().collect();
+    let command = args.get(1).map(|arg| arg.as_str());
+
+    if let Err(e) = setup_logger(command.is_some()) {
         eprintln!("Error setting up logger: {}", e);
         return;
     }
@@ -286,10 +358,42 @@ async fn main() {
         }
     };
 
+    match command {
+        None => {}
+        Some("replay-mail") => {
+            if let Err(e) = replay::replay_mail(&args[2..], configs).await {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        Some(other) => {
+            error!("Unknown command '{}', available: replay-mail", other);
+            std::process::exit(1);
+        }
+    }
+
     log_startup_config(&configs);
 
+    let source_status = source_status::SourceStatus::default();
+
+    // status changes of vehicles with `divera_id` go to the first Divera API
+    let divera = configs.general.apis.iter().find(|api| matches!(api.api, config::general::ApiType::Divera));
+    let divera_sync = configs.general.vehicles.values().any(|vehicle| vehicle.divera_id.is_some());
+    let divera_changes = match divera {
+        Some(api) if divera_sync && configs.general.alarm => {
+            info!("Fahrzeugstatus wird mit Divera '{}' synchronisiert", api.name);
+            let (send_changes, recv_changes) = flume::unbounded();
+            let divera = apis::divera_v2::DiveraV2 { name: api.name.clone(), api_key: api.api_key.clone() };
+            tokio::spawn(vehicle_status::sync_divera(recv_changes, divera));
+            Some(send_changes)
+        }
+        _ => None,
+    };
+    let vehicle_status = vehicle_status::VehicleStatus::new(configs.general.vehicles.clone(), divera_changes);
+
     // Start healthcheck server in the background
-    tokio::spawn(start_healthcheck_server(8112));
+    tokio::spawn(start_healthcheck_server(8112, source_status.clone(), vehicle_status.clone()));
 
     if configs.general.delay > 0 {
         info!(
@@ -302,7 +406,53 @@ async fn main() {
     // channel to send and receive alarms
     let (send_alarms, recv_alarms) = flume::unbounded();
 
-    let alarm_handler = AlarmHandler::new(recv_alarms, configs.general, configs.alarm_templates.clone());
+    let archive = configs.general.archive.as_ref().map(archive::Archive::new);
+    if let Some(archive_config) = &configs.general.archive {
+        info!("Rohdaten werden archiviert in {}", archive_config.directory);
+    }
+
+    let geocoder = match configs.general.geocoding.as_ref().map(geocoder::Geocoder::new).transpose() {
+        Ok(geocoder) => geocoder,
+        Err(e) => {
+            error!("Error loading config: {}", e);
+            panic!();
+        }
+    };
+
+    let enrichment = match configs.general.enrichment.as_ref().map(enrichment::Enrichment::new).transpose() {
+        Ok(enrichment) => enrichment,
+        Err(e) => {
+            error!("Error loading config: {}", e);
+            panic!();
+        }
+    };
+
+    let router = match configs.general.routing.as_ref().map(routing::Router::new).transpose() {
+        Ok(router) => router,
+        Err(e) => {
+            error!("Error loading config: {}", e);
+            panic!();
+        }
+    };
+
+    let stichwoerter = match stichwort::StichwortCatalogue::new(&configs.general.stichwoerter) {
+        Ok(stichwoerter) => stichwoerter,
+        Err(e) => {
+            error!("Error loading config: {}", e);
+            panic!();
+        }
+    };
+
+    let alarm_handler = AlarmHandler::new(
+        recv_alarms,
+        configs.general,
+        configs.alarm_templates.clone(),
+        geocoder,
+        enrichment,
+        router,
+        stichwoerter,
+    )
+    .with_vehicle_status(vehicle_status.clone());
 
     alarm_handler.check_api_connections().await;
     alarm_handler.start();
@@ -314,9 +464,13 @@ async fn main() {
             continue;
         }
         let send_alarms = send_alarms.clone();
+        let archive = archive.clone();
         thread::spawn(move || {
-            let mail_handler = mail_handler::MailHandler::new(mail_source_config, send_alarms, false);
-            mail_handler.start();
+            let name = mail_source_config.name.clone();
+            match mail_handler::MailHandler::new(mail_source_config, send_alarms, false, archive) {
+                Ok(mail_handler) => mail_handler.start(),
+                Err(e) => error!("Mail source '{}' could not be started: {}", name, e),
+            }
         });
     }
 
@@ -327,9 +481,15 @@ async fn main() {
             continue;
         }
         let send_alarms = send_alarms.clone();
+        let archive = archive.clone();
+        let source_status = source_status.clone();
+        let vehicle_status = vehicle_status.clone();
         thread::spawn(move || {
-            let serial_handler = serial_handler::SerialHandler::new(serial_source_config, send_alarms, true);
-            serial_handler.start();
+            let name = serial_source_config.name.clone();
+            match serial_handler::SerialHandler::new(serial_source_config, send_alarms, true, archive, source_status, vehicle_status) {
+                Ok(serial_handler) => serial_handler.start(),
+                Err(e) => error!("Serial source '{}' could not be started: {}", name, e),
+            }
         });
     }
 
@@ -0,0 +1,175 @@
+use std::cmp::Reverse;
+use log::{debug, info};
+use regex::Regex;
+use crate::alarm::Alarm;
+use crate::config::alarm_sources::SerialConfig;
+
+/// Extracts Stichwort, address and free text from the pager text of a serial
+/// source: the first matching `text_rules` entry (by priority), otherwise the
+/// longest `alarm_list` entry found as a whole word.
+pub struct PagerText {
+    /// name and pattern, highest priority first
+    rules: Vec<(String, Regex)>,
+    /// entry and whole word pattern, longest entry first
+    alarm_list: Vec<(String, Regex)>,
+}
+
+impl PagerText {
+    pub fn new(config: &SerialConfig) -> Result<Self, String> {
+        let mut rules: Vec<(i32, String, Regex)> = vec![];
+        for (i, rule) in config.text_rules.iter().enumerate() {
+            let name = rule.name.clone().unwrap_or_else(|| format!("#{}", i + 1));
+            let regex = Regex::new(&rule.pattern).map_err(|e| format!("Text rule '{}': {}", name, e))?;
+            rules.push((rule.priority, name, regex));
+        }
+        // stable, equal priorities keep the config order
+        rules.sort_by_key(|rule| Reverse(rule.0));
+
+        let mut alarm_list = vec![];
+        for entry in &config.alarm_list {
+            let words: Vec<String> = entry.split_whitespace().map(regex::escape).collect();
+            if words.is_empty() {
+                continue;
+            }
+            // `Brand 1` must not match inside `Brand 12`
+            let pattern = format!(r"(?:^|[^\p{{L}}\d]){}(?:$|[^\p{{L}}\d])", words.join(r"\s+"));
+            let regex = Regex::new(&pattern).map_err(|e| format!("alarm_list '{}': {}", entry, e))?;
+            alarm_list.push((entry.trim().to_string(), regex));
+        }
+        alarm_list.sort_by_key(|entry| Reverse(entry.0.len()));
+
+        Ok(Self {
+            rules: rules.into_iter().map(|(_, name, regex)| (name, regex)).collect(),
+            alarm_list,
+        })
+    }
+
+    /// Sets the fields found in `alarm.text`. Groups of the rule that did not
+    /// match leave the field unchanged; the `text` group replaces the alarm text.
+    pub fn apply(&self, alarm: &mut Alarm) {
+        let text = alarm.text.clone();
+        for (name, regex) in &self.rules {
+            let captures = match regex.captures(&text) {
+                Some(captures) => captures,
+                None => continue,
+            };
+            let group = |group: &str| {
+                captures.name(group).map(|m| m.as_str().split_whitespace().collect::<Vec<_>>().join(" ")).filter(|value| !value.is_empty())
+            };
+
+            if let Some(stichwort) = group("stichwort") {
+                alarm.set_stichwort(stichwort.clone());
+                alarm.set_title(stichwort);
+            }
+            if let Some(title) = group("title") {
+                alarm.set_title(title);
+            }
+            if let Some(street) = group("street") {
+                alarm.address.set_street(street);
+            }
+            if let Some(city) = group("city") {
+                alarm.address.set_city(city);
+            }
+            if let Some(object) = group("object") {
+                alarm.address.set_object(object);
+            }
+            if let Some(info) = group("info") {
+                alarm.address.set_info(info);
+            }
+            if let Some(free_text) = captures.name("text").map(|m| m.as_str().trim().to_string()) {
+                alarm.set_text(free_text);
+            }
+            info!("Text rule '{}' trifft zu: Stichwort '{}'", name, alarm.stichwort);
+            return;
+        }
+
+        match self.alarm_list.iter().find(|(_, regex)| regex.is_match(&text)) {
+            Some((stichwort, _)) => {
+                alarm.set_title(stichwort.clone());
+                alarm.set_stichwort(stichwort.clone());
+            }
+            None => debug!("No text rule matches '{}'", text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pager_text(text_rules: serde_json::Value, alarm_list: &[&str]) -> PagerText {
+        let config: SerialConfig = serde_json::from_value(serde_json::json!({
+            "name": "Pager", "active": true, "port": "/dev/null", "delimiter": "\\r\\n",
+            "alarm_list": alarm_list, "rics": {}, "text_rules": text_rules,
+        }))
+        .unwrap();
+        PagerText::new(&config).unwrap()
+    }
+
+    fn apply(pager_text: &PagerText, text: &str) -> Alarm {
+        let mut alarm = Alarm::new();
+        alarm.set_title("Einsatz".to_string());
+        alarm.set_text(text.to_string());
+        pager_text.apply(&mut alarm);
+        alarm
+    }
+
+    #[test]
+    fn higher_priority_wins() {
+        let rules = serde_json::json!([
+            { "name": "any", "pattern": r"^(?P<stichwort>\S+)" },
+            { "name": "brand", "pattern": r"^(?P<stichwort>B\d)\b", "priority": 10 },
+        ]);
+        assert_eq!(apply(&pager_text(rules, &[]), "B3 Wohnhaus").stichwort, "B3");
+    }
+
+    #[test]
+    fn equal_priority_keeps_config_order() {
+        let rules = serde_json::json!([
+            { "name": "first", "pattern": r"^(?P<stichwort>\S+ \S+)", "priority": 5 },
+            { "name": "second", "pattern": r"^(?P<stichwort>\S+)", "priority": 5 },
+        ]);
+        assert_eq!(apply(&pager_text(rules, &[]), "TH 1 Baum").stichwort, "TH 1");
+    }
+
+    #[test]
+    fn longest_alarm_list_entry_wins() {
+        let pager_text = pager_text(serde_json::json!([]), &["Brand 1", "Brand 12", "Brand"]);
+        assert_eq!(apply(&pager_text, "Brand 12 Lagerhalle").stichwort, "Brand 12");
+        assert_eq!(apply(&pager_text, "Brand 1 Mülltonne").stichwort, "Brand 1");
+        assert_eq!(apply(&pager_text, "Alarm Brand   1").stichwort, "Brand 1");
+    }
+
+    #[test]
+    fn alarm_list_matches_whole_words() {
+        let pager_text = pager_text(serde_json::json!([]), &["Brand 1", "TH"]);
+        let alarm = apply(&pager_text, "Brand 13 Wohnhaus, THW angefordert");
+        assert_eq!(alarm.stichwort, "");
+        assert_eq!(alarm.title, "Einsatz");
+        assert_eq!(apply(&pager_text, "(Brand 1) Wiese").stichwort, "Brand 1");
+        assert_eq!(apply(&pager_text, "Einsatz: TH").stichwort, "TH");
+    }
+
+    #[test]
+    fn groups_set_fields() {
+        let rules = serde_json::json!([{
+            "pattern": r"^(?P<stichwort>\S+)\s+(?P<title>[^/]+?)\s*/\s*(?P<street>[^,]+),\s*(?P<city>[^/]+?)\s*(?:/\s*(?P<object>[^/]*?)\s*)?(?:/\s*(?P<info>[^/]*?)\s*)?(?:#\s*(?P<text>.*))?$",
+        }]);
+        let pager_text = pager_text(rules, &["B3"]);
+
+        let alarm = apply(&pager_text, "B3 Brand  Wohnhaus / Hauptstraße 5, Musterstadt / Sonnenhof / 2. OG # Personen vermisst");
+        assert_eq!(alarm.stichwort, "B3");
+        assert_eq!(alarm.title, "Brand Wohnhaus");
+        assert_eq!(alarm.address.street, "Hauptstraße 5");
+        assert_eq!(alarm.address.city, "Musterstadt");
+        assert_eq!(alarm.address.object, "Sonnenhof");
+        assert_eq!(alarm.address.info, "2. OG");
+        assert_eq!(alarm.text, "Personen vermisst");
+
+        // groups that did not match leave the fields and the text unchanged
+        let alarm = apply(&pager_text, "B3 Brand / Waldweg 1, Musterdorf");
+        assert_eq!(alarm.title, "Brand");
+        assert_eq!(alarm.address.object, "");
+        assert_eq!(alarm.text, "B3 Brand / Waldweg 1, Musterdorf");
+    }
+}
@@ -0,0 +1,155 @@
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+use native_tls::TlsConnector;
+
+trait Pop3Stream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> Pop3Stream for T {}
+
+/// Minimal POP3 client (RFC 1939) with UIDL support, just enough to fetch alarm mails.
+pub struct Pop3Client {
+    stream: BufReader<Box<dyn Pop3Stream>>,
+}
+
+#[derive(Debug)]
+pub enum Pop3Error {
+    Io(io::Error),
+    Tls(String),
+    Server(String),
+}
+
+impl std::fmt::Display for Pop3Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pop3Error::Io(e) => write!(f, "io error: {}", e),
+            Pop3Error::Tls(e) => write!(f, "tls error: {}", e),
+            Pop3Error::Server(e) => write!(f, "server error: {}", e),
+        }
+    }
+}
+
+impl From<io::Error> for Pop3Error {
+    fn from(e: io::Error) -> Self {
+        Pop3Error::Io(e)
+    }
+}
+
+pub type Pop3Result<T> = Result<T, Pop3Error>;
+
+impl Pop3Client {
+    pub fn connect(host: &str, port: u16, tls: bool) -> Pop3Result<Self> {
+        let tcp = TcpStream::connect((host, port))?;
+        tcp.set_read_timeout(Some(Duration::from_secs(60)))?;
+        tcp.set_write_timeout(Some(Duration::from_secs(60)))?;
+
+        let stream: Box<dyn Pop3Stream> = if tls {
+            let connector = TlsConnector::new().map_err(|e| Pop3Error::Tls(e.to_string()))?;
+            let tls_stream = connector
+                .connect(host, tcp)
+                .map_err(|e| Pop3Error::Tls(e.to_string()))?;
+            Box::new(tls_stream)
+        } else {
+            Box::new(tcp)
+        };
+
+        let mut client = Self { stream: BufReader::new(stream) };
+        // server greeting
+        client.read_status()?;
+        Ok(client)
+    }
+
+    pub fn login(&mut self, user: &str, password: &str) -> Pop3Result<()> {
+        self.command(&format!("USER {}", user))?;
+        self.command(&format!("PASS {}", password))?;
+        Ok(())
+    }
+
+    /// Returns `(message number, unique id)` for every message in the maildrop.
+    pub fn uidl(&mut self) -> Pop3Result<Vec<(u32, String)>> {
+        self.command("UIDL")?;
+        let lines = self.read_multiline()?;
+
+        let mut result = vec![];
+        for line in lines {
+            let line = String::from_utf8_lossy(&line).to_string();
+            let mut parts = line.split_whitespace();
+            if let (Some(number), Some(uid)) = (parts.next(), parts.next()) {
+                if let Ok(number) = number.parse::<u32>() {
+                    result.push((number, uid.to_string()));
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Fetches the raw RFC822 message.
+    pub fn retr(&mut self, number: u32) -> Pop3Result<Vec<u8>> {
+        self.command(&format!("RETR {}", number))?;
+        let lines = self.read_multiline()?;
+
+        let mut message = vec![];
+        for line in lines {
+            message.extend_from_slice(&line);
+            message.extend_from_slice(b"\r\n");
+        }
+        Ok(message)
+    }
+
+    pub fn dele(&mut self, number: u32) -> Pop3Result<()> {
+        self.command(&format!("DELE {}", number))?;
+        Ok(())
+    }
+
+    /// Ends the session, messages marked with `dele` are removed by the server now.
+    pub fn quit(mut self) -> Pop3Result<()> {
+        self.command("QUIT")?;
+        Ok(())
+    }
+
+    fn command(&mut self, command: &str) -> Pop3Result<String> {
+        let stream = self.stream.get_mut();
+        stream.write_all(command.as_bytes())?;
+        stream.write_all(b"\r\n")?;
+        stream.flush()?;
+        self.read_status()
+    }
+
+    fn read_line(&mut self) -> Pop3Result<Vec<u8>> {
+        let mut line = vec![];
+        let read = self.stream.read_until(b'\n', &mut line)?;
+        if read == 0 {
+            return Err(Pop3Error::Io(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed")));
+        }
+        while line.last() == Some(&b'\n') || line.last() == Some(&b'\r') {
+            line.pop();
+        }
+        Ok(line)
+    }
+
+    fn read_status(&mut self) -> Pop3Result<String> {
+        let line = String::from_utf8_lossy(&self.read_line()?).to_string();
+        if line.starts_with("+OK") {
+            Ok(line)
+        } else {
+            Err(Pop3Error::Server(line))
+        }
+    }
+
+    fn read_multiline(&mut self) -> Pop3Result<Vec<Vec<u8>>> {
+        let mut lines = vec![];
+        loop {
+            let line = self.read_line()?;
+            if line == b"." {
+                break;
+            }
+            // undo dot-stuffing
+            if line.starts_with(b"..") {
+                lines.push(line[1..].to_vec());
+            } else {
+                lines.push(line);
+            }
+        }
+        Ok(lines)
+    }
+}
@@ -0,0 +1,181 @@
+use std::fmt::Write;
+use std::fs;
+use log::info;
+use crate::alarm_handler::{apply_templates, geocode_alarm, route_alarm, LOOKUP_DEADLINE};
+use crate::config::alarm_sources::MailConfig;
+use crate::config::Configs;
+use crate::enrichment::Enrichment;
+use crate::geocoder::Geocoder;
+use crate::routing::Router;
+use crate::stichwort::StichwortCatalogue;
+use crate::alarm::Alarm;
+use crate::mail_handler::MailHandler;
+
+const USAGE: &str = "Usage: alarm-server replay-mail <file.eml> [--source <name>] [--lookups]";
+
+/// `<file> [--source <name>] [--lookups]`
+fn parse_args(args: &[String]) -> Result<(String, Option<String>, bool), String> {
+    let mut file = None;
+    let mut source = None;
+    let mut lookups = false;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--source" | "-s" => {
+                source = Some(args.next().ok_or(USAGE)?.clone());
+            }
+            "--lookups" => lookups = true,
+            _ if file.is_none() => file = Some(arg.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    Ok((file.ok_or(USAGE)?, source, lookups))
+}
+
+/// `alarm-server replay-mail <file.eml> --source <name> [--lookups]`
+///
+/// Runs a saved mail through the pipeline of a mail source and prints the
+/// resulting alarm plus the APIs and webhooks it would trigger. Nothing is dispatched.
+pub async fn replay_mail(args: &[String], configs: Configs) -> Result<(), String> {
+    let (file, source, lookups) = parse_args(args)?;
+    let mail_sources = configs.alarm_sources.mail_sources.clone();
+
+    let mail_config = match source {
+        Some(name) => mail_sources
+            .into_iter()
+            .find(|s| s.name == name)
+            .ok_or_else(|| format!("Mail source '{}' not found", name))?,
+        None if mail_sources.len() == 1 => mail_sources.into_iter().next().unwrap(),
+        None => return Err(format!("{} (more than one mail source configured)", USAGE)),
+    };
+
+    let raw = fs::read(&file).map_err(|e| format!("Could not read {}: {}", file, e))?;
+    print!("{}", replay(&raw, mail_config, &configs, lookups).await?);
+    Ok(())
+}
+
+/// The printed result of a replayed mail.
+async fn replay(raw: &[u8], mail_config: MailConfig, configs: &Configs, lookups: bool) -> Result<String, String> {
+    // alarms are only built, nobody listens on the channel
+    let (send_alarms, _recv_alarms) = flume::unbounded();
+    let mail_handler = MailHandler::new(mail_config, send_alarms, true, None)?;
+
+    let alarm = mail_handler.replay(raw)?;
+    let alarm = run_pipeline(alarm, configs, lookups).await?;
+    render(&alarm, configs)
+}
+
+/// Stichwort catalogue, enrichment and templates. Geocoding and routing may
+/// ask Nominatim/OSRM, so they only run with `lookups`.
+async fn run_pipeline(mut alarm: Alarm, configs: &Configs, lookups: bool) -> Result<Alarm, String> {
+    StichwortCatalogue::new(&configs.general.stichwoerter)?.apply(&mut alarm);
+    let deadline = tokio::time::Instant::now() + LOOKUP_DEADLINE;
+    if lookups {
+        if let Some(geocoding) = &configs.general.geocoding {
+            geocode_alarm(&mut alarm, &Geocoder::new(geocoding)?, deadline).await;
+        }
+    }
+    if let Some(enrichment) = &configs.general.enrichment {
+        Enrichment::new(enrichment)?.enrich(&mut alarm);
+    }
+    if lookups {
+        if let Some(routing) = &configs.general.routing {
+            route_alarm(&mut alarm, &Router::new(routing)?, deadline).await;
+        }
+    } else if configs.general.geocoding.is_some() || configs.general.routing.is_some() {
+        info!("Geocoding und Routing übersprungen (--lookups für Anfragen an Nominatim/OSRM)");
+    }
+    apply_templates(&mut alarm, &configs.alarm_templates)?;
+    Ok(alarm)
+}
+
+/// The alarm JSON with the APIs and webhooks it would trigger.
+fn render(alarm: &Alarm, configs: &Configs) -> Result<String, String> {
+    let mut out = serde_json::to_string_pretty(alarm).map_err(|e| format!("Failed to serialize alarm: {}", e))?;
+    out.push('\n');
+
+    if !configs.general.alarm {
+        out.push_str("\nNote: general.alarm is false, nothing would be dispatched.\n");
+    }
+
+    out.push_str("\nAPIs:\n");
+    let mut api_names: Vec<&String> = alarm.receiver.keys().collect();
+    api_names.sort();
+    for api_name in api_names {
+        let receivers = &alarm.receiver[api_name];
+        let configured = configs.general.apis.iter().any(|api| &api.name == api_name);
+        writeln!(
+            out,
+            "  {}{}: groups {:?}, vehicles {:?}, members {:?}",
+            api_name,
+            if configured { "" } else { " (not configured, skipped)" },
+            receivers.groups,
+            receivers.vehicles,
+            receivers.members
+        )
+        .unwrap();
+    }
+
+    out.push_str("\nWebhooks:\n");
+    for webhook in &alarm.webhooks {
+        writeln!(out, "  {}", webhook).unwrap();
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn configs() -> Configs {
+        Configs {
+            alarm_sources: serde_json::from_value(json!({ "mail_sources": [], "serial_sources": [] })).unwrap(),
+            alarm_templates: serde_json::from_value(json!({
+                "default": { "Divera": { "groups": ["Alle"] } },
+                "hlf": { "Telegram": { "groups": ["HLF"] }, "webhooks": ["https://example.org/hook"] },
+            }))
+            .unwrap(),
+            general: serde_json::from_value(json!({
+                "apis": [{ "name": "Divera", "api": "Divera", "api_key": "" }],
+                "alarm_window_seconds": 60, "source_priority": [], "alarm": true,
+                // never asked without --lookups, nothing listens there
+                "geocoding": { "nominatim_url": "http://127.0.0.1:9" },
+                "routing": { "station": { "lat": 50.1, "lon": 8.6 }, "osrm_url": "http://127.0.0.1:9" },
+            }))
+            .unwrap(),
+        }
+    }
+
+    #[tokio::test]
+    async fn replay_cobra_mail() {
+        let mail_config: MailConfig = serde_json::from_value(json!({
+            "name": "Leitstelle", "active": true, "host": "", "port": 993, "tls": true, "max_age": 60,
+            "alarm_sender": "alarm@leitstelle.example", "alarm_subject": "Alarm 2026-004711 B3",
+            "alarm_template_keywords": { "FL MST 1/46-1": "hlf" }, "mail_schema": "Cobra",
+            "stichwoerter": {}, "ignore_units": [], "polling": false, "polling_interval": 0, "idle": false,
+        }))
+        .unwrap();
+        let raw = fs::read(format!("{}/fixtures/mail/cobra.eml", env!("CARGO_MANIFEST_DIR"))).unwrap();
+
+        let output = replay(&raw, mail_config, &configs(), false).await.unwrap();
+        let (json, lists) = output.split_once("\n\nAPIs:\n").unwrap();
+
+        let alarm: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(alarm["id"], "2026-004711");
+        assert_eq!(alarm["stichwort"], "B3");
+        assert_eq!(alarm["address"]["street"], "Hauptstraße 5");
+        assert_eq!(alarm["units"], json!(["FL MST 1/46-1", "FL MST 1/33-1", "RTW 1/83-2"]));
+        assert_eq!(alarm["route"], serde_json::Value::Null);
+
+        assert_eq!(
+            lists,
+            "  Divera: groups [\"Alle\"], vehicles [], members []\n\
+             \x20 Telegram (not configured, skipped): groups [\"HLF\"], vehicles [], members []\n\
+             \nWebhooks:\n  https://example.org/hook\n"
+        );
+    }
+}
@@ -0,0 +1,410 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs;
+use std::sync::Arc;
+use std::time::Duration;
+use log::{debug, info, warn};
+use reqwest::Client;
+use serde_json::Value;
+use crate::alarm::Route;
+use crate::config::general::RoutingConfig;
+use crate::coordinates::distance_m;
+
+/// Offline road graph from a GeoJSON file of LineStrings. Roads are connected
+/// where they share a vertex; one-way restrictions are ignored.
+struct RoadGraph {
+    nodes: Vec<(f64, f64)>,
+    /// neighbor, length in m, travel time in s
+    edges: Vec<Vec<(usize, f64, f64)>>,
+    /// node ids by grid cell, see `grid_cell`
+    grid: HashMap<(i64, i64), Vec<usize>>,
+}
+
+/// Calculates distance and driving time from the station to the alarm address,
+/// via an OSRM compatible endpoint, the offline road graph or a straight line estimate.
+pub struct Router {
+    station: (f64, f64),
+    osrm_url: Option<String>,
+    graph: Option<Arc<RoadGraph>>,
+    average_speed_kmh: f64,
+    detour_factor: f64,
+    client: Client,
+}
+
+/// Points of `Route::geometry`, enough for a map of the approach.
+const MAX_ROUTE_POINTS: usize = 200;
+
+/// Size of the node index cells in degrees, about 1.1 km north-south.
+const GRID_CELL_DEG: f64 = 0.01;
+
+fn grid_cell(lat: f64, lon: f64) -> (i64, i64) {
+    ((lat / GRID_CELL_DEG).floor() as i64, (lon / GRID_CELL_DEG).floor() as i64)
+}
+
+/// Speed in km/h for an OSM `highway` class, `maxspeed` wins if it is a positive number.
+fn road_speed(properties: &Value) -> f64 {
+    let maxspeed = properties["maxspeed"].as_str().and_then(|v| v.trim().parse::<f64>().ok()).or(properties["maxspeed"].as_f64());
+    if let Some(maxspeed) = maxspeed.filter(|speed| speed.is_finite() && *speed > 0.0) {
+        return maxspeed;
+    }
+    match properties["highway"].as_str().unwrap_or_default() {
+        "motorway" | "motorway_link" => 100.0,
+        "trunk" | "trunk_link" => 80.0,
+        "primary" | "primary_link" => 70.0,
+        "secondary" | "secondary_link" => 60.0,
+        "tertiary" | "tertiary_link" => 50.0,
+        "residential" | "unclassified" => 30.0,
+        "service" | "living_street" | "track" => 15.0,
+        _ => 40.0,
+    }
+}
+
+/// Every n-th point so at most `MAX_ROUTE_POINTS` remain, start and end are kept.
+fn cap_points(geometry: Vec<[f64; 2]>) -> Vec<[f64; 2]> {
+    if geometry.len() <= MAX_ROUTE_POINTS {
+        return geometry;
+    }
+    let step = (geometry.len() - 1) as f64 / (MAX_ROUTE_POINTS - 1) as f64;
+    (0..MAX_ROUTE_POINTS).map(|i| geometry[((i as f64 * step).round() as usize).min(geometry.len() - 1)]).collect()
+}
+
+/// Dijkstra queue entry, smallest time first.
+struct QueueEntry {
+    time: f64,
+    node: usize,
+}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.time == other.time
+    }
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.time.total_cmp(&self.time)
+    }
+}
+
+impl RoadGraph {
+    fn parse(content: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(content).map_err(|e| e.to_string())?;
+        let features = value["features"].as_array().ok_or("no 'features' array")?;
+
+        let mut graph = RoadGraph { nodes: vec![], edges: vec![], grid: HashMap::new() };
+        // vertices with the same coordinates (to ~10 cm) are one node
+        let mut node_ids: HashMap<(i64, i64), usize> = HashMap::new();
+        let mut node_id = |graph: &mut RoadGraph, lat: f64, lon: f64| -> usize {
+            let key = ((lat * 1e6).round() as i64, (lon * 1e6).round() as i64);
+            *node_ids.entry(key).or_insert_with(|| {
+                graph.nodes.push((lat, lon));
+                graph.edges.push(vec![]);
+                graph.nodes.len() - 1
+            })
+        };
+
+        for feature in features {
+            let geometry = &feature["geometry"];
+            let lines: Vec<&Value> = match geometry["type"].as_str() {
+                Some("LineString") => vec![&geometry["coordinates"]],
+                Some("MultiLineString") => geometry["coordinates"].as_array().map(|lines| lines.iter().collect()).unwrap_or_default(),
+                _ => continue,
+            };
+            let speed_ms = road_speed(&feature["properties"]) / 3.6;
+
+            for line in lines {
+                let points: Vec<(f64, f64)> = line
+                    .as_array()
+                    .map(|points| {
+                        points
+                            .iter()
+                            .filter_map(|point| Some((point[1].as_f64()?, point[0].as_f64()?)))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                for pair in points.windows(2) {
+                    let from = node_id(&mut graph, pair[0].0, pair[0].1);
+                    let to = node_id(&mut graph, pair[1].0, pair[1].1);
+                    let length = distance_m(pair[0].0, pair[0].1, pair[1].0, pair[1].1);
+                    let time = length / speed_ms;
+                    graph.edges[from].push((to, length, time));
+                    graph.edges[to].push((from, length, time));
+                }
+            }
+        }
+
+        for (idx, &(lat, lon)) in graph.nodes.iter().enumerate() {
+            graph.grid.entry(grid_cell(lat, lon)).or_default().push(idx);
+        }
+
+        Ok(graph)
+    }
+
+    /// Searches the grid cells in rings around the position until no closer
+    /// node can be in the next ring.
+    fn nearest_node(&self, lat: f64, lon: f64) -> Option<usize> {
+        let (row, col) = grid_cell(lat, lon);
+        let max_ring = self.grid.keys().map(|(r, c)| (r - row).abs().max((c - col).abs())).max()?;
+        // a cell is at least this wide (east-west, shrinking with the latitude)
+        let cell_m = GRID_CELL_DEG * 111_320.0 * lat.to_radians().cos().max(0.01);
+
+        let mut nearest: Option<(usize, f64)> = None;
+        for ring in 0..=max_ring {
+            if nearest.is_some_and(|(_, distance)| (ring - 1) as f64 * cell_m > distance) {
+                break;
+            }
+            for r in row - ring..=row + ring {
+                for c in col - ring..=col + ring {
+                    if (r - row).abs() != ring && (c - col).abs() != ring {
+                        continue;
+                    }
+                    for &idx in self.grid.get(&(r, c)).into_iter().flatten() {
+                        let distance = distance_m(lat, lon, self.nodes[idx].0, self.nodes[idx].1);
+                        if nearest.is_none_or(|(_, best)| distance < best) {
+                            nearest = Some((idx, distance));
+                        }
+                    }
+                }
+            }
+        }
+        nearest.map(|(idx, _)| idx)
+    }
+
+    fn route(&self, from: (f64, f64), to: (f64, f64)) -> Option<Route> {
+        let start = self.nearest_node(from.0, from.1)?;
+        let target = self.nearest_node(to.0, to.1)?;
+
+        let mut times = vec![f64::INFINITY; self.nodes.len()];
+        let mut previous: Vec<Option<(usize, f64)>> = vec![None; self.nodes.len()];
+        let mut queue = BinaryHeap::new();
+        times[start] = 0.0;
+        queue.push(QueueEntry { time: 0.0, node: start });
+
+        while let Some(QueueEntry { time, node }) = queue.pop() {
+            if node == target {
+                break;
+            }
+            if time > times[node] {
+                continue;
+            }
+            for &(next, length, edge_time) in &self.edges[node] {
+                let next_time = time + edge_time;
+                if next_time < times[next] {
+                    times[next] = next_time;
+                    previous[next] = Some((node, length));
+                    queue.push(QueueEntry { time: next_time, node: next });
+                }
+            }
+        }
+
+        if !times[target].is_finite() {
+            return None;
+        }
+
+        let mut geometry = vec![self.nodes[target]];
+        let mut distance = 0.0;
+        let mut node = target;
+        while let Some((prev, length)) = previous[node] {
+            distance += length;
+            geometry.push(self.nodes[prev]);
+            node = prev;
+        }
+        geometry.reverse();
+
+        // way from the station and to the address to the nearest road
+        let access = distance_m(from.0, from.1, self.nodes[start].0, self.nodes[start].1)
+            + distance_m(to.0, to.1, self.nodes[target].0, self.nodes[target].1);
+
+        Some(Route {
+            distance_m: distance + access,
+            duration_s: times[target] + access / (15.0 / 3.6),
+            source: "graph".to_string(),
+            geometry: std::iter::once(from).chain(geometry).chain(std::iter::once(to)).map(|(lat, lon)| [lat, lon]).collect(),
+        })
+    }
+}
+
+impl Router {
+    pub fn new(config: &RoutingConfig) -> Result<Self, String> {
+        let graph = match &config.graph {
+            Some(path) => {
+                let content = fs::read_to_string(path).map_err(|e| format!("Could not read routing graph {}: {}", path, e))?;
+                let graph = RoadGraph::parse(&content).map_err(|e| format!("Routing graph {}: {}", path, e))?;
+                info!("Routing Graph geladen aus {}: {} Knoten", path, graph.nodes.len());
+                Some(Arc::new(graph))
+            }
+            None => None,
+        };
+
+        let client = Client::builder()
+            .timeout(Duration::from_secs(config.timeout_seconds))
+            .build()
+            .map_err(|e| format!("Could not create routing client: {}", e))?;
+
+        Ok(Self {
+            station: (config.station.lat, config.station.lon),
+            osrm_url: config.osrm_url.as_ref().map(|url| url.trim_end_matches('/').to_string()),
+            graph,
+            average_speed_kmh: config.average_speed_kmh,
+            detour_factor: config.detour_factor,
+            client,
+        })
+    }
+
+    /// Route from the station to `lat`/`lon`. Falls back to the straight line
+    /// distance times `detour_factor` at `average_speed_kmh`.
+    pub async fn route(&self, lat: f64, lon: f64) -> Route {
+        if let Some(url) = &self.osrm_url {
+            match self.route_osrm(url, lat, lon).await {
+                Ok(route) => return route,
+                Err(e) => warn!("Routing via OSRM failed: {}", e),
+            }
+        }
+
+        if let Some(graph) = &self.graph {
+            // Dijkstra on a district graph takes a while, off the async workers so a deadline can cancel the wait
+            let (graph, station) = (graph.clone(), self.station);
+            match tokio::task::spawn_blocking(move || graph.route(station, (lat, lon))).await {
+                Ok(Some(route)) => return Route { geometry: cap_points(route.geometry), ..route },
+                Ok(None) => warn!("No route in the routing graph to {}, {}", lat, lon),
+                Err(e) => warn!("Routing in the graph failed: {}", e),
+            }
+        }
+
+        self.estimate(lat, lon)
+    }
+
+    /// Straight line distance times `detour_factor` at `average_speed_kmh`.
+    pub fn estimate(&self, lat: f64, lon: f64) -> Route {
+        let distance = distance_m(self.station.0, self.station.1, lat, lon) * self.detour_factor;
+        debug!("Route estimated from straight line distance");
+        Route {
+            distance_m: distance,
+            duration_s: distance / (self.average_speed_kmh / 3.6),
+            source: "estimate".to_string(),
+            geometry: vec![[self.station.0, self.station.1], [lat, lon]],
+        }
+    }
+
+    async fn route_osrm(&self, url: &str, lat: f64, lon: f64) -> Result<Route, String> {
+        let url = format!(
+            "{}/route/v1/driving/{},{};{},{}?overview=simplified&geometries=geojson",
+            url, self.station.1, self.station.0, lon, lat
+        );
+        debug!("OSRM request: {}", url);
+
+        let response = self.client.get(&url).send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("HTTP {}", response.status()));
+        }
+        let value: Value = response.json().await.map_err(|e| e.to_string())?;
+        let route = &value["routes"][0];
+
+        match (route["distance"].as_f64(), route["duration"].as_f64()) {
+            (Some(distance_m), Some(duration_s)) => Ok(Route {
+                distance_m,
+                duration_s,
+                source: "osrm".to_string(),
+                geometry: cap_points(
+                    route["geometry"]["coordinates"]
+                        .as_array()
+                        .map(|points| {
+                            points
+                                .iter()
+                                .filter_map(|point| Some([point[1].as_f64()?, point[0].as_f64()?]))
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                ),
+            }),
+            _ => Err(format!("No route in response: {}", value["code"])),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn maxspeed_must_be_positive() {
+        assert_eq!(road_speed(&json!({ "highway": "primary", "maxspeed": "50" })), 50.0);
+        assert_eq!(road_speed(&json!({ "highway": "primary", "maxspeed": 30 })), 30.0);
+        assert_eq!(road_speed(&json!({ "highway": "primary", "maxspeed": "0" })), 70.0);
+        assert_eq!(road_speed(&json!({ "highway": "residential", "maxspeed": -10 })), 30.0);
+        assert_eq!(road_speed(&json!({ "highway": "residential", "maxspeed": "none" })), 30.0);
+    }
+
+    #[test]
+    fn zero_maxspeed_does_not_block_the_graph() {
+        let geojson = json!({ "features": [
+            { "geometry": { "type": "LineString", "coordinates": [[8.0, 50.0], [8.01, 50.0]] }, "properties": { "highway": "tertiary", "maxspeed": "0" } },
+        ]});
+        let graph = RoadGraph::parse(&geojson.to_string()).unwrap();
+        let route = graph.route((50.0, 8.0), (50.0, 8.01)).unwrap();
+        assert!(route.duration_s.is_finite());
+        assert!((route.duration_s - route.distance_m / (50.0 / 3.6)).abs() < 1.0);
+    }
+
+    #[test]
+    fn nearest_node_from_the_grid() {
+        // a grid of roads over about 20 x 15 km
+        let features: Vec<Value> = (0..20)
+            .map(|i| {
+                let lat = 50.0 + i as f64 * 0.01;
+                json!({ "geometry": { "type": "LineString", "coordinates": (0..20).map(|j| [8.0 + j as f64 * 0.01, lat]).collect::<Vec<_>>() }, "properties": {} })
+            })
+            .collect();
+        let graph = RoadGraph::parse(&json!({ "features": features }).to_string()).unwrap();
+        assert!(graph.grid.len() > 100);
+
+        let linear = |lat: f64, lon: f64| {
+            (0..graph.nodes.len()).min_by(|a, b| {
+                distance_m(lat, lon, graph.nodes[*a].0, graph.nodes[*a].1).total_cmp(&distance_m(lat, lon, graph.nodes[*b].0, graph.nodes[*b].1))
+            })
+        };
+        for (lat, lon) in [(50.0, 8.0), (50.0549, 8.0951), (50.1234, 8.1766), (49.9, 7.9), (50.5, 8.05), (50.1, 9.5)] {
+            assert_eq!(graph.nearest_node(lat, lon), linear(lat, lon), "{}, {}", lat, lon);
+        }
+        assert_eq!(RoadGraph::parse(r#"{ "features": [] }"#).unwrap().nearest_node(50.0, 8.0), None);
+    }
+
+    #[tokio::test]
+    async fn graph_route_runs_off_the_async_workers() {
+        let geojson = json!({ "features": [
+            { "geometry": { "type": "LineString", "coordinates": [[8.0, 50.0], [8.01, 50.0], [8.02, 50.0]] }, "properties": { "highway": "tertiary" } },
+        ]});
+        let path = std::env::temp_dir().join(format!("alarm-server-routing-{}.geojson", std::process::id()));
+        fs::write(&path, geojson.to_string()).unwrap();
+        let config: RoutingConfig = serde_json::from_value(json!({
+            "station": { "lat": 50.0, "lon": 8.0 }, "graph": path.to_string_lossy(),
+        }))
+        .unwrap();
+        let router = Router::new(&config).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        let route = router.route(50.0, 8.02).await;
+        assert_eq!(route.source, "graph");
+        assert!((route.distance_m - distance_m(50.0, 8.0, 50.0, 8.02)).abs() < 1.0);
+    }
+
+    #[test]
+    fn geometry_is_capped() {
+        let geometry: Vec<[f64; 2]> = (0..1000).map(|i| [50.0, 8.0 + i as f64 * 0.001]).collect();
+        let capped = cap_points(geometry.clone());
+        assert_eq!(capped.len(), MAX_ROUTE_POINTS);
+        assert_eq!(capped.first(), geometry.first());
+        assert_eq!(capped.last(), geometry.last());
+        assert_eq!(cap_points(geometry[..10].to_vec()).len(), 10);
+    }
+}
@@ -1,126 +1,533 @@
 use flume::Sender;
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 use std::io::Read;
-use std::io::BufRead;
 use std::io;
-use encoding_rs::ISO_8859_2;
-use crate::alarm::{Alarm, DmeData};
-use crate::config::alarm_sources::SerialConfig;
+use encoding_rs::Encoding;
+use crate::alarm::Alarm;
+use crate::archive::Archive;
+use crate::config::alarm_sources::{NetworkMode, SerialConfig, UsbConfig};
+use crate::dme_decoder::{decoder_for, dme_charset, DmeDecoder};
+use crate::frame_reader::FrameReader;
+use crate::pager_text::PagerText;
+use crate::stream_port::{ListenerPort, PipePort, TcpPort, UdpPort};
+use crate::source_status::SourceStatus;
+use crate::vehicle_status::{parse_status, VehicleStatus};
 use log::{debug, error, info, warn};
-use serialport::SerialPort;
+use serialport::SerialPortType;
 
 pub struct SerialHandler {
     config: SerialConfig,
     send_alarms: Sender<Alarm>,
     debug: bool,
+    archive: Option<Archive>,
+    decoder: Box<dyn DmeDecoder>,
+    charset: &'static Encoding,
+    pager_text: PagerText,
+    status: SourceStatus,
+    vehicles: VehicleStatus,
 }
 
 impl SerialHandler {
-    pub fn new(config: SerialConfig, send_alarms: Sender<Alarm>, debug: bool) -> Self {
-        Self {
+    pub fn new(config: SerialConfig, send_alarms: Sender<Alarm>, debug: bool, archive: Option<Archive>, status: SourceStatus, vehicles: VehicleStatus) -> Result<Self, String> {
+        let decoder = decoder_for(&config)?;
+        let charset = dme_charset(config.charset.as_deref())?;
+        let pager_text = PagerText::new(&config)?;
+        Ok(Self {
             config,
             send_alarms,
             debug,
-        }
+            archive,
+            decoder,
+            charset,
+            pager_text,
+            status,
+            vehicles,
+        })
+    }
+
+    /// Delimiter with the escapes `\r`, `\n` and `\0` replaced.
+    pub fn delimiter(&self) -> Vec<u8> {
+        self.config.delimiter.replace("\\r", "\r").replace("\\n", "\n").replace("\\0", "\0").into_bytes()
     }
 
+    /// Opens the port and reads frames. A port that can not be opened or
+    /// fails while reading is reopened with backoff, so unplugging the
+    /// adapter (or a lost network connection) does not disable the source.
     pub fn start(&self) {
-        let port_name = &self.config.port;
-        let baud_rate = self.config.baudrate;
-        let delimiter = self.config.delimiter.clone().replace("\\r", "\r").replace("\\n", "\n").replace("\\0", "\0");
-
-        let mut port = match serialport::new(port_name, baud_rate)
-            .timeout(Duration::from_millis(10))
-            .open() {
-            Ok(port) => port,
-            Err(e) => {
-                error!("Failed to open serial port: {:?}", e);
-                return;
+        let name = &self.config.name;
+        let min_backoff = Duration::from_secs(self.config.reconnect_min_seconds.max(1));
+        let max_backoff = Duration::from_secs(self.config.reconnect_max_seconds).max(min_backoff);
+        let mut backoff = min_backoff;
+
+        self.status.set_disconnected(name, "not connected yet");
+
+        loop {
+            match self.open_port() {
+                Ok((port_name, mut port)) => {
+                    info!("Serial source '{}' verbunden: {}", name, port_name);
+                    self.status.set_connected(name, &port_name);
+
+                    let (e, received) = self.read_frames(&mut port);
+                    // a port that fails again without data keeps the backoff growing
+                    if received {
+                        backoff = min_backoff;
+                    }
+                    if e.kind() == io::ErrorKind::UnexpectedEof && self.config.pipe.as_deref() == Some("-") {
+                        info!("Serial source '{}': Ende der Eingabe, Quelle beendet", name);
+                        self.status.set_disconnected(name, "end of input");
+                        return;
+                    }
+                    error!("Error reading from {}: {}", port_name, e);
+                    self.status.set_disconnected(name, &e.to_string());
+                }
+                Err(e) => {
+                    error!("{}", e);
+                    self.status.set_disconnected(name, &e);
+                }
+            }
+
+            warn!("Serial source '{}': reconnect in {}s", name, backoff.as_secs());
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(max_backoff);
+        }
+    }
+
+    /// The serial port, the pipe or the network connection of `network`. Returns a
+    /// description of the port and the reader.
+    fn open_port(&self) -> Result<(String, Box<dyn Read>), String> {
+        if let Some(pipe) = &self.config.pipe {
+            return Ok((pipe.clone(), Box::new(PipePort::open(pipe)?)));
+        }
+
+        let network = match &self.config.network {
+            Some(network) => network,
+            None => {
+                let port_name = self.resolve_port()?;
+                let port = serialport::new(&port_name, self.config.baudrate)
+                    .timeout(Duration::from_millis(10))
+                    .open()
+                    .map_err(|e| format!("Failed to open serial port {}: {}", port_name, e))?;
+                return Ok((format!("{} ({} Baud)", port_name, self.config.baudrate), Box::new(port)));
             }
         };
 
-        info!("Serial port opened: {}, Baudrate: {}", port_name, baud_rate);
+        match network.mode {
+            NetworkMode::TcpClient => {
+                let port = TcpPort::connect(&network.address)?;
+                Ok((format!("tcp://{}", network.address), Box::new(port)))
+            }
+            NetworkMode::Udp => {
+                let port = UdpPort::bind(&network.address)?;
+                Ok((format!("udp://{}", network.address), Box::new(port)))
+            }
+            NetworkMode::TcpListener => {
+                let port = ListenerPort::bind(&network.address)?;
+                info!("Serial source '{}' wartet auf Verbindungen an {}", self.config.name, network.address);
+                Ok((format!("tcp://{} (listener)", network.address), Box::new(port)))
+            }
+        }
+    }
+
+    /// `port`, or the device with the configured USB ids.
+    fn resolve_port(&self) -> Result<String, String> {
+        match &self.config.usb {
+            Some(usb) => find_usb_port(usb),
+            None => Ok(self.config.port.clone()),
+        }
+    }
 
-        let mut temp_buffer: Vec<u8> = vec![0; 1024];
-        let mut buffer: Vec<u8> = Vec::new();
-        let end_sequence: &[u8] = delimiter.as_bytes();
+    /// Reads until the port fails, returns the error and whether any bytes were received.
+    fn read_frames(&self, port: &mut dyn Read) -> (io::Error, bool) {
+        // large enough for a whole UDP datagram
+        let mut temp_buffer: Vec<u8> = vec![0; 65536];
+        let mut frame_reader = self.frame_reader();
+
+        // alarm collecting the RICs of the aggregation window, with its first frame time
+        let mut pending: Option<(Alarm, Instant)> = None;
+        let mut received = false;
 
         loop {
-            match port.read(temp_buffer.as_mut_slice()) {
+            let frames = match port.read(temp_buffer.as_mut_slice()) {
                 Ok(bytes_read) => {
                     if bytes_read > 0 {
                         debug!("Buffer: {:02X?}", &temp_buffer[..bytes_read]);
-                        buffer.extend_from_slice(&temp_buffer[..bytes_read]);
+                        received = true;
+                    }
+                    frame_reader.push(&temp_buffer[..bytes_read], Instant::now())
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => frame_reader.poll(Instant::now()).into_iter().collect(),
+                Err(e) => {
+                    if let Some((alarm, _)) = pending.take() {
+                        self.send_alarm(alarm);
+                    }
+                    return (e, received);
+                }
+            };
 
-                        // Check if the buffer contains the delimiter
-                        if buffer.ends_with(end_sequence) {
-                            let (decoded_buffer, _, _) = ISO_8859_2.decode(&buffer);
-                            debug!("Received: {}", decoded_buffer);
+            for frame in frames {
+                self.status.frame_received(&self.config.name);
+                let alarm = match self.build_alarm(&frame.data) {
+                    Ok(alarm) => alarm,
+                    Err(e) => {
+                        warn!("{}", e);
+                        None
+                    }
+                };
 
-                            // Handle the data
-                            let (final_decoded, _, _) = ISO_8859_2.decode(&buffer);
-                            self.handle_dme_data(final_decoded.to_string());
+                if let Some(archive) = &self.archive {
+                    let alarm_id = alarm.as_ref().map(|alarm| alarm.id.as_str()).unwrap_or("");
+                    archive.store(&self.config.name, alarm_id, "dme", &frame.raw(&self.delimiter()));
+                }
 
-                            buffer.clear();
-                        }
-                    }
+                if let Some(alarm) = alarm {
+                    self.aggregate(&mut pending, alarm, Instant::now());
                 }
-                Err(ref e) if e.kind() == io::ErrorKind::TimedOut => (),
-                Err(e) => {
-                    error!("Error reading from serial port: {:?}", e);
-                    break;
+            }
+
+            let window = Duration::from_secs(self.config.aggregation_seconds);
+            if pending.as_ref().is_some_and(|(_, first)| first.elapsed() >= window) {
+                if let Some((alarm, _)) = pending.take() {
+                    self.send_alarm(alarm);
+                }
+            }
+        }
+    }
+
+    /// Adds the alarm to the pending one if it arrived within `aggregation_seconds`,
+    /// otherwise the pending alarm is sent and this one starts a new window.
+    fn aggregate(&self, pending: &mut Option<(Alarm, Instant)>, alarm: Alarm, now: Instant) {
+        let window = Duration::from_secs(self.config.aggregation_seconds);
+        match pending {
+            Some((first, since)) if now.duration_since(*since) < window => {
+                info!("RIC {} zur Alarmierung hinzugefügt", alarm.dme_data.rics.join(", "));
+                merge_alarm(first, alarm);
+            }
+            _ => {
+                if let Some((alarm, _)) = pending.take() {
+                    self.send_alarm(alarm);
+                }
+                if window.is_zero() {
+                    self.send_alarm(alarm);
+                } else {
+                    *pending = Some((alarm, now));
                 }
             }
         }
     }
 
-    fn handle_dme_data(&self, data: String) {
+    fn send_alarm(&self, alarm: Alarm) {
+        info!("Alarm: {:?}", alarm);
+        if let Err(e) = self.send_alarms.send(alarm) {
+            error!("Failed to send alarm: {:?}", e);
+        }
+    }
+
+    /// Frame splitting with the limits of the source.
+    pub fn frame_reader(&self) -> FrameReader {
+        FrameReader::new(
+            self.delimiter(),
+            Duration::from_millis(self.config.frame_timeout_ms),
+            self.config.max_frame_size,
+        )
+    }
+
+    /// `None` for a vehicle status without alarm.
+    fn build_alarm(&self, frame: &[u8]) -> Result<Option<Alarm>, String> {
+        let (data, _, had_errors) = self.charset.decode(frame);
         debug!("Received data: {}", data);
+        if had_errors {
+            warn!("Received data is not valid {}", self.charset.name());
+        }
 
-        let mut alarm = Alarm::new();
+        let dme_data = self
+            .decoder
+            .decode(&data)
+            .map_err(|e| format!("Could not decode frame ({}): {}", self.config.protocol, e))?;
+        debug!("Decoded: {:?}", dme_data);
 
-        alarm.origin = self.config.name.clone();
+        if dme_data.kind == "FMS" {
+            match parse_status(&dme_data.status) {
+                Some(status) => {
+                    self.vehicles.update(&dme_data.ric, status, &self.config.name);
+                }
+                None => debug!("FMS {}: kein Fahrzeugstatus ({})", dme_data.ric, dme_data.content),
+            }
+        }
 
-        // split the data after each line break
-        let lines: Vec<&str> = data.trim().lines().collect();
+        let mut alarm = Alarm::new();
+        alarm.origin = self.config.name.clone();
 
-        if lines.len() < 3 {
-            warn!("Received data is too short");
-            return;
+        let ric_key = format!("{}{}", dme_data.ric, dme_data.sub_address);
+        let mapping = self.config.rics.get(&ric_key).or_else(|| self.config.rics.get(&dme_data.ric));
+        match mapping.map(|mapping| mapping.templates(&dme_data.sub_address)) {
+            Some(Some(templates)) => alarm.template_names.extend(templates),
+            Some(None) => {
+                debug!("RIC {}: Unteradresse {} nicht in sub_addresses, kein Alarm", dme_data.ric, dme_data.sub_address);
+                return Ok(None);
+            }
+            // status changes of every vehicle would be alarms otherwise
+            None if dme_data.kind == "FMS" => return Ok(None),
+            None => {
+                debug!("RIC {} ist keinem Template zugeordnet", ric_key);
+                alarm.set_title("Einsatz".to_string());
+            }
         }
 
-        for i in 0..lines.len() {
-            debug!("Line {}: {}", i, lines[i]);
+        let text = dme_data.content.clone();
+        let mut dme_data = dme_data;
+        dme_data.rics = vec![ric_key];
+        alarm.set_text(text.clone());
+        alarm.set_dme_data(dme_data);
+
+        self.pager_text.apply(&mut alarm);
+
+        debug!("Alarm: {:?}", alarm);
+        Ok(Some(alarm))
+    }
+}
+
+/// Templates, RICs and a different text of `alarm` are added to `first`.
+fn merge_alarm(first: &mut Alarm, alarm: Alarm) {
+    for template in alarm.template_names {
+        if !first.template_names.contains(&template) {
+            first.add_template_name(template);
+        }
+    }
+    for ric in alarm.dme_data.rics {
+        if !first.dme_data.rics.contains(&ric) {
+            first.dme_data.rics.push(ric);
         }
+    }
+    if !alarm.text.is_empty() && !first.text.contains(&alarm.text) {
+        first.add_to_text(format!("\n{}", alarm.text));
+    }
+    if first.stichwort.is_empty() && !alarm.stichwort.is_empty() {
+        first.set_stichwort(alarm.stichwort);
+        first.set_title(alarm.title);
+    } else if first.title == "Einsatz" && alarm.title != "Einsatz" {
+        first.set_title(alarm.title);
+    }
+}
 
-        let ric = lines[1].to_string();
-        let text = lines[2].to_string();
+/// Device path of the first USB serial port with the ids of `usb`.
+fn find_usb_port(usb: &UsbConfig) -> Result<String, String> {
+    let (vid, pid) = usb.ids()?;
+    let ports = serialport::available_ports().map_err(|e| format!("Could not list serial ports: {}", e))?;
 
-        alarm.set_dme_data(DmeData {
-            date: lines[0].to_string(),
-            ric: ric.clone(),
-            content: text.clone(),
-        });
+    ports
+        .into_iter()
+        .find(|port| match &port.port_type {
+            SerialPortType::UsbPort(info) => {
+                info.vid == vid
+                    && info.pid == pid
+                    && usb.serial_number.as_ref().is_none_or(|serial| info.serial_number.as_deref() == Some(serial.as_str()))
+            }
+            _ => false,
+        })
+        .map(|port| port.port_name)
+        .ok_or_else(|| {
+            format!(
+                "No USB serial port {:04x}:{:04x}{} found",
+                vid,
+                pid,
+                usb.serial_number.as_ref().map(|serial| format!(" ({})", serial)).unwrap_or_default()
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::fs;
+    use crate::config::general::ArchiveConfig;
 
-        if let Some(alarm_template) = self.config.rics.get(&ric) {
-            alarm.template_names.push(alarm_template.clone());
-        } else {
-            alarm.set_title("Einsatz".to_string());
+    /// Source config with the `Regex` protocol (`<ric> <text>`), `fields` is
+    /// merged into the JSON.
+    fn config(fields: serde_json::Value) -> SerialConfig {
+        let mut config = serde_json::json!({
+            "name": "Pager",
+            "active": true,
+            "port": "/dev/null",
+            "delimiter": "\\r\\n",
+            "alarm_list": [],
+            "rics": {},
+            "protocol": "Regex",
+            "frame_pattern": r"^(?P<ric>\d+)(?P<sub>[A-D])?\s*(?P<text>.*)$",
+            "frame_timeout_ms": 50,
+        });
+        for (key, value) in fields.as_object().unwrap() {
+            config[key] = value.clone();
         }
+        serde_json::from_value(config).unwrap()
+    }
 
-        alarm.set_text(text);
+    fn handler(config: SerialConfig, archive: Option<Archive>) -> (SerialHandler, flume::Receiver<Alarm>) {
+        let (send_alarms, recv_alarms) = flume::unbounded();
+        let handler = SerialHandler::new(config, send_alarms, false, archive, SourceStatus::default(), VehicleStatus::default()).unwrap();
+        (handler, recv_alarms)
+    }
 
-        info!("Alarm: {:?}", alarm);
+    enum Step {
+        Data(&'static [u8]),
+        /// no bytes for this long, the port reports `TimedOut` like a serial port
+        Pause(Duration),
+    }
+
+    /// In-memory port, fails with `UnexpectedEof` after the last step.
+    struct ScriptedPort(VecDeque<Step>);
 
-        for stichwort in self.config.alarm_list.clone() {
-            if lines[2].contains(stichwort.as_str()) {
-                alarm.title = stichwort.to_string();
+    impl Read for ScriptedPort {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.pop_front() {
+                Some(Step::Data(data)) => {
+                    buf[..data.len()].copy_from_slice(data);
+                    Ok(data.len())
+                }
+                Some(Step::Pause(pause)) => {
+                    thread::sleep(pause);
+                    Err(io::ErrorKind::TimedOut.into())
+                }
+                None => Err(io::ErrorKind::UnexpectedEof.into()),
             }
         }
+    }
 
-        // Populate alarm with data
-        if let Err(e) = self.send_alarms.send(alarm) {
-            error!("Failed to send alarm: {:?}", e);
-        }
+    fn texts(recv_alarms: &flume::Receiver<Alarm>) -> Vec<String> {
+        recv_alarms.try_iter().map(|alarm| alarm.text).collect()
+    }
+
+    #[test]
+    fn read_frames_from_in_memory_port() {
+        let (handler, recv_alarms) = handler(config(serde_json::json!({})), None);
+        let mut port = ScriptedPort(VecDeque::from([
+            Step::Data(b"1234567 B3 Wohnhaus\r\n7654321 TH"),
+            Step::Data(b" 1\r"),
+            Step::Data(b"\n"),
+            Step::Pause(Duration::from_millis(5)),
+        ]));
+
+        let (e, received) = handler.read_frames(&mut port);
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        assert!(received);
+        assert_eq!(texts(&recv_alarms), vec!["B3 Wohnhaus", "TH 1"]);
+    }
+
+    #[test]
+    fn read_frames_completes_frame_by_timeout() {
+        let (handler, recv_alarms) = handler(config(serde_json::json!({})), None);
+        let mut port = ScriptedPort(VecDeque::from([
+            Step::Data(b"1234567 B3 Wohnhaus"),
+            Step::Pause(Duration::from_millis(80)),
+            Step::Pause(Duration::from_millis(1)),
+            Step::Data(b"7654321 TH 1\r\n"),
+        ]));
+
+        handler.read_frames(&mut port);
+        assert_eq!(texts(&recv_alarms), vec!["B3 Wohnhaus", "TH 1"]);
+    }
+
+    #[test]
+    fn read_frames_without_data() {
+        let (handler, recv_alarms) = handler(config(serde_json::json!({})), None);
+        let (_, received) = handler.read_frames(&mut ScriptedPort(VecDeque::new()));
+        assert!(!received);
+        assert!(texts(&recv_alarms).is_empty());
+    }
+
+    #[test]
+    fn archive_stores_received_bytes() {
+        let directory = std::env::temp_dir().join(format!("alarm-server-archive-{}", std::process::id()));
+        let archive = Archive::new(&ArchiveConfig { directory: directory.display().to_string(), max_age_days: 0, max_files: 0 });
+        let (handler, _recv_alarms) = handler(config(serde_json::json!({})), Some(archive));
+        let mut port = ScriptedPort(VecDeque::from([
+            Step::Data(b"1234567 B3\r\n"),
+            Step::Pause(Duration::from_millis(10)),
+            Step::Data(b"7654321 TH 1"),
+            Step::Pause(Duration::from_millis(80)),
+            Step::Pause(Duration::from_millis(1)),
+        ]));
+
+        handler.read_frames(&mut port);
+
+        let mut stored: Vec<Vec<u8>> = fs::read_dir(directory.join("Pager"))
+            .unwrap()
+            .map(|entry| fs::read(entry.unwrap().path()).unwrap())
+            .collect();
+        stored.sort();
+        fs::remove_dir_all(&directory).unwrap();
+        // the frame completed by timeout had no delimiter
+        assert_eq!(stored, vec![b"1234567 B3\r\n".to_vec(), b"7654321 TH 1".to_vec()]);
+    }
+
+    fn alarm(text: &str, title: &str, stichwort: &str, templates: &[&str], ric: &str) -> Alarm {
+        let mut alarm = Alarm::new();
+        alarm.set_text(text.to_string());
+        alarm.set_title(title.to_string());
+        alarm.stichwort = stichwort.to_string();
+        alarm.template_names = templates.iter().map(|t| t.to_string()).collect();
+        alarm.dme_data.rics = vec![ric.to_string()];
+        alarm
+    }
+
+    #[test]
+    fn filtered_sub_address_is_no_alarm() {
+        let rics = serde_json::json!({
+            "1234567": { "templates": ["Vollalarm"], "sub_addresses": ["A", "B"] },
+            "7654321": "Probe",
+        });
+        let (handler, _) = handler(config(serde_json::json!({ "rics": rics })), None);
+
+        let alarm = handler.build_alarm(b"1234567A B3 Wohnhaus").unwrap().unwrap();
+        assert_eq!(alarm.template_names, vec!["Vollalarm"]);
+        assert!(handler.build_alarm(b"1234567C B3 Wohnhaus").unwrap().is_none());
+        assert_eq!(handler.build_alarm(b"7654321D Probealarm").unwrap().unwrap().template_names, vec!["Probe"]);
+
+        // an unknown RIC is still an alarm without template
+        let unknown = handler.build_alarm(b"1111111C Unbekannt").unwrap().unwrap();
+        assert!(unknown.template_names.is_empty());
+        assert_eq!(unknown.title, "Einsatz");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn merge_alarm_adds_templates_rics_and_text() {
+        let mut first = alarm("Brand Wohnhaus", "Einsatz", "", &["Vollalarm"], "1234567A");
+        merge_alarm(&mut first, alarm("Brand Wohnhaus", "Einsatz", "", &["Vollalarm", "DLK"], "1234568A"));
+        merge_alarm(&mut first, alarm("Personen vermisst", "Brand 3", "B3", &["DLK"], "1234569B"));
+        merge_alarm(&mut first, alarm("", "Technische Hilfe", "TH", &[], "1234570A"));
+
+        assert_eq!(first.template_names, vec!["Vollalarm", "DLK"]);
+        assert_eq!(first.dme_data.rics, vec!["1234567A", "1234568A", "1234569B", "1234570A"]);
+        assert_eq!(first.text, "Brand Wohnhaus\nPersonen vermisst");
+        // the first Stichwort wins
+        assert_eq!((first.stichwort.as_str(), first.title.as_str()), ("B3", "Brand 3"));
+    }
+
+    #[test]
+    fn aggregate_within_window() {
+        let (handler, recv_alarms) = handler(config(serde_json::json!({ "aggregation_seconds": 10 })), None);
+        let start = Instant::now();
+        let mut pending = None;
+        handler.aggregate(&mut pending, alarm("B3", "Einsatz", "", &["Vollalarm"], "1234567A"), start);
+        handler.aggregate(&mut pending, alarm("DLK", "Einsatz", "", &["DLK"], "1234568A"), start + Duration::from_secs(9));
+        assert!(recv_alarms.try_recv().is_err());
+
+        // a RIC after the window sends the collected alarm and starts a new one
+        handler.aggregate(&mut pending, alarm("TH 1", "Einsatz", "", &[], "7654321A"), start + Duration::from_secs(10));
+        let sent = recv_alarms.try_recv().unwrap();
+        assert_eq!(sent.dme_data.rics, vec!["1234567A", "1234568A"]);
+        assert_eq!(sent.template_names, vec!["Vollalarm", "DLK"]);
+        assert_eq!(pending.unwrap().0.dme_data.rics, vec!["7654321A"]);
+    }
+
+    #[test]
+    fn aggregate_disabled_sends_at_once() {
+        let (handler, recv_alarms) = handler(config(serde_json::json!({ "aggregation_seconds": 0 })), None);
+        let mut pending = None;
+        handler.aggregate(&mut pending, alarm("B3", "Einsatz", "", &[], "1234567A"), Instant::now());
+        handler.aggregate(&mut pending, alarm("DLK", "Einsatz", "", &[], "1234568A"), Instant::now());
+        assert!(pending.is_none());
+        assert_eq!(texts(&recv_alarms), vec!["B3", "DLK"]);
+    }
+}
@@ -0,0 +1,194 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use log::{debug, error, info, warn};
+
+/// Largest message accepted via DATA, alarm mails are far below this.
+const MAX_MESSAGE_SIZE: usize = 25 * 1024 * 1024;
+
+/// Minimal SMTP receiver (RFC 5321 subset without AUTH/STARTTLS) for alarm mails
+/// delivered directly over the LAN.
+pub struct SmtpServer {
+    name: String,
+    hostname: String,
+    allow_sender: Arc<dyn Fn(&str) -> bool + Send + Sync>,
+    on_message: Arc<dyn Fn(Vec<u8>) + Send + Sync>,
+}
+
+impl SmtpServer {
+    pub fn new(
+        name: String,
+        allow_sender: impl Fn(&str) -> bool + Send + Sync + 'static,
+        on_message: impl Fn(Vec<u8>) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            hostname: format!("alarm-server-{}", name.replace(char::is_whitespace, "-")),
+            name,
+            allow_sender: Arc::new(allow_sender),
+            on_message: Arc::new(on_message),
+        }
+    }
+
+    /// Binds `bind`:`port` and accepts connections forever.
+    pub fn listen(self, bind: &str, port: u16) -> io::Result<()> {
+        let listener = TcpListener::bind((bind, port))?;
+        info!("{} SMTP listener on {}:{}", self.name, bind, port);
+        self.serve(listener);
+        Ok(())
+    }
+
+    /// Accepts connections forever, each one is handled in its own thread.
+    pub fn serve(self, listener: TcpListener) {
+        let server = Arc::new(self);
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let server = Arc::clone(&server);
+                    thread::spawn(move || {
+                        let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+                        debug!("{} SMTP connection from {}", server.name, peer);
+                        if let Err(e) = server.handle_connection(stream) {
+                            warn!("{} SMTP connection from {} failed: {}", server.name, peer, e);
+                        }
+                    });
+                }
+                Err(e) => error!("{} SMTP accept failed: {}", server.name, e),
+            }
+        }
+    }
+
+    fn handle_connection(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(300)))?;
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+
+        reply(&mut writer, &format!("220 {} ESMTP ready", self.hostname))?;
+
+        let mut sender: Option<String> = None;
+        let mut has_recipient = false;
+
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            let line = line.trim_end_matches(['\r', '\n']);
+            let upper = line.to_uppercase();
+
+            if upper.starts_with("EHLO") {
+                reply(&mut writer, &format!("250-{}", self.hostname))?;
+                reply(&mut writer, &format!("250-SIZE {}", MAX_MESSAGE_SIZE))?;
+                reply(&mut writer, "250 8BITMIME")?;
+            } else if upper.starts_with("HELO") {
+                reply(&mut writer, &format!("250 {}", self.hostname))?;
+            } else if upper.starts_with("MAIL FROM:") {
+                let address = extract_address(&line["MAIL FROM:".len()..]);
+                if (self.allow_sender)(&address) {
+                    sender = Some(address);
+                    has_recipient = false;
+                    reply(&mut writer, "250 OK")?;
+                } else {
+                    warn!("{} SMTP sender rejected: <{}>", self.name, address);
+                    reply(&mut writer, "550 Sender not allowed")?;
+                }
+            } else if upper.starts_with("RCPT TO:") {
+                if sender.is_none() {
+                    reply(&mut writer, "503 Need MAIL first")?;
+                } else {
+                    has_recipient = true;
+                    reply(&mut writer, "250 OK")?;
+                }
+            } else if upper == "DATA" {
+                if sender.is_none() || !has_recipient {
+                    reply(&mut writer, "503 Need MAIL and RCPT first")?;
+                    continue;
+                }
+                reply(&mut writer, "354 End data with <CR><LF>.<CR><LF>")?;
+
+                match read_data(&mut reader)? {
+                    Some(message) => {
+                        info!(
+                            "{} SMTP mail received from <{}> ({} bytes)",
+                            self.name,
+                            sender.as_deref().unwrap_or(""),
+                            message.len()
+                        );
+                        (self.on_message)(message);
+                        reply(&mut writer, "250 OK")?;
+                    }
+                    None => reply(&mut writer, "552 Message too large")?,
+                }
+                sender = None;
+                has_recipient = false;
+            } else if upper == "RSET" {
+                sender = None;
+                has_recipient = false;
+                reply(&mut writer, "250 OK")?;
+            } else if upper == "NOOP" {
+                reply(&mut writer, "250 OK")?;
+            } else if upper == "QUIT" {
+                reply(&mut writer, "221 Bye")?;
+                return Ok(());
+            } else {
+                reply(&mut writer, "502 Command not implemented")?;
+            }
+        }
+    }
+}
+
+fn reply(writer: &mut impl Write, line: &str) -> io::Result<()> {
+    writer.write_all(line.as_bytes())?;
+    writer.write_all(b"\r\n")?;
+    writer.flush()
+}
+
+/// `<user@example.org> SIZE=123` -> `user@example.org`
+fn extract_address(argument: &str) -> String {
+    let argument = argument.trim();
+    match (argument.find('<'), argument.find('>')) {
+        (Some(start), Some(end)) if start < end => argument[start + 1..end].to_string(),
+        _ => argument.split_whitespace().next().unwrap_or("").to_string(),
+    }
+}
+
+/// Reads the DATA section up to the terminating dot. Returns `None` if the
+/// message exceeded `MAX_MESSAGE_SIZE` (the rest is still consumed).
+fn read_data(reader: &mut impl BufRead) -> io::Result<Option<Vec<u8>>> {
+    let mut message = vec![];
+    let mut too_large = false;
+
+    loop {
+        let mut line = vec![];
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "connection closed during DATA"));
+        }
+
+        let content = line.strip_suffix(b"\n").unwrap_or(&line);
+        let content = content.strip_suffix(b"\r").unwrap_or(content);
+        if content == b"." {
+            break;
+        }
+
+        if too_large {
+            continue;
+        }
+
+        // undo dot-stuffing
+        let content = content.strip_prefix(b".").filter(|_| content.starts_with(b"..")).unwrap_or(content);
+        message.extend_from_slice(content);
+        message.extend_from_slice(b"\r\n");
+
+        if message.len() > MAX_MESSAGE_SIZE {
+            too_large = true;
+            message.clear();
+        }
+    }
+
+    if too_large {
+        Ok(None)
+    } else {
+        Ok(Some(message))
+    }
+}
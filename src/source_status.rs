@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use chrono::{DateTime, Utc};
+use serde_derive::Serialize;
+
+/// Connection state of one alarm source.
+#[derive(Debug, Clone, Serialize)]
+pub struct SourceState {
+    pub connected: bool,
+    /// device path or address in use
+    pub port: String,
+    /// last error while disconnected
+    pub message: String,
+    pub since: DateTime<Utc>,
+    pub last_frame: Option<DateTime<Utc>>,
+    pub reconnects: u64,
+}
+
+/// Shared between the source threads and the health endpoint.
+#[derive(Clone, Default)]
+pub struct SourceStatus {
+    sources: Arc<Mutex<HashMap<String, SourceState>>>,
+}
+
+impl SourceStatus {
+    fn update(&self, name: &str, update: impl FnOnce(&mut SourceState)) {
+        let mut sources = self.sources.lock().unwrap_or_else(|e| e.into_inner());
+        let state = sources.entry(name.to_string()).or_insert_with(|| SourceState {
+            connected: false,
+            port: String::new(),
+            message: String::new(),
+            since: Utc::now(),
+            last_frame: None,
+            reconnects: 0,
+        });
+        update(state);
+    }
+
+    pub fn set_connected(&self, name: &str, port: &str) {
+        self.update(name, |state| {
+            // the port is only empty before the first connection
+            if !state.port.is_empty() {
+                state.reconnects += 1;
+            }
+            state.connected = true;
+            state.port = port.to_string();
+            state.message.clear();
+            state.since = Utc::now();
+        });
+    }
+
+    pub fn set_disconnected(&self, name: &str, message: &str) {
+        self.update(name, |state| {
+            if state.connected || state.message.is_empty() {
+                state.since = Utc::now();
+            }
+            state.connected = false;
+            state.message = message.to_string();
+        });
+    }
+
+    pub fn frame_received(&self, name: &str) {
+        self.update(name, |state| state.last_frame = Some(Utc::now()));
+    }
+
+    /// All sources connected, and the states by source name.
+    pub fn snapshot(&self) -> (bool, HashMap<String, SourceState>) {
+        let sources = self.sources.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        (sources.values().all(|state| state.connected), sources)
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use log::{debug, info};
+use regex::Regex;
+use crate::alarm::{Alarm, StichwortInfo};
+use crate::config::general::StichwortConfig;
+
+/// Global Stichwort catalogue of `general.json`. The Stichwort found by the
+/// parser is looked up, for sources without one (DME) the alarm text is searched.
+pub struct StichwortCatalogue {
+    /// keyword, whole word pattern and entry, longest keyword first
+    entries: Vec<(String, Regex, StichwortConfig)>,
+}
+
+/// Upper case, single spaces.
+fn normalize(value: &str) -> String {
+    value.split_whitespace().collect::<Vec<_>>().join(" ").to_uppercase()
+}
+
+impl StichwortCatalogue {
+    pub fn new(stichwoerter: &HashMap<String, StichwortConfig>) -> Result<Self, String> {
+        let mut entries = vec![];
+        for (keyword, config) in stichwoerter {
+            let words: Vec<String> = keyword.split_whitespace().map(regex::escape).collect();
+            if words.is_empty() {
+                return Err("Empty Stichwort in catalogue".to_string());
+            }
+            let pattern = format!(r"(?i)(?:^|[^\p{{L}}\d]){}(?:$|[^\p{{L}}\d])", words.join(r"\s+"));
+            let regex = Regex::new(&pattern).map_err(|e| format!("Stichwort '{}': {}", keyword, e))?;
+            entries.push((keyword.trim().to_string(), regex, config.clone()));
+        }
+        // `B2 Y` wins over `B2`
+        entries.sort_by(|a, b| b.0.len().cmp(&a.0.len()).then_with(|| a.0.cmp(&b.0)));
+
+        if !entries.is_empty() {
+            info!("Stichwort Katalog geladen: {} Einträge", entries.len());
+        }
+        Ok(Self { entries })
+    }
+
+    /// Exact match of the Stichwort first, then the keyword as a word in the
+    /// Stichwort (e.g. `B2 - Wohnungsbrand`), or in the text if there is none.
+    fn find(&self, alarm: &Alarm) -> Option<&(String, Regex, StichwortConfig)> {
+        let stichwort = normalize(&alarm.stichwort);
+        if stichwort.is_empty() {
+            return self.entries.iter().find(|entry| entry.1.is_match(&alarm.text));
+        }
+        self.entries
+            .iter()
+            .find(|entry| normalize(&entry.0) == stichwort)
+            .or_else(|| self.entries.iter().find(|entry| entry.1.is_match(&alarm.stichwort)))
+    }
+
+    /// Sets `stichwort_info`, adds the default templates of the entry and
+    /// replaces a generic title (empty, `Einsatz` or the bare Stichwort).
+    pub fn apply(&self, alarm: &mut Alarm) {
+        let (keyword, _, config) = match self.find(alarm) {
+            Some(entry) => entry,
+            None => {
+                if !alarm.stichwort.is_empty() {
+                    debug!("Stichwort '{}' nicht im Katalog", alarm.stichwort);
+                }
+                return;
+            }
+        };
+
+        let title = config.title.clone().unwrap_or_else(|| keyword.clone());
+        let generic_title = alarm.title.trim().is_empty()
+            || alarm.title.trim() == "Einsatz"
+            || normalize(&alarm.title) == normalize(&alarm.stichwort);
+        if generic_title {
+            alarm.set_title(title.clone());
+        }
+        if alarm.stichwort.is_empty() {
+            alarm.set_stichwort(keyword.clone());
+        }
+
+        for template in &config.templates {
+            if !alarm.template_names.contains(template) {
+                alarm.add_template_name(template.clone());
+            }
+        }
+
+        info!("Stichwort {}: {} ({:?})", keyword, title, config.priority);
+        alarm.stichwort_info = Some(StichwortInfo {
+            keyword: keyword.clone(),
+            title,
+            category: config.category.clone().unwrap_or_default(),
+            priority: config.priority,
+            color: config.color.clone().unwrap_or_default(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::general::AlarmPriority;
+
+    fn catalogue() -> StichwortCatalogue {
+        let stichwoerter: HashMap<String, StichwortConfig> = serde_json::from_value(serde_json::json!({
+            "Brand 1": { "title": "Kleinbrand", "category": "Brand", "priority": "silent", "templates": ["klein"] },
+            "Brand 12": { "title": "Großbrand", "category": "Brand", "color": "#ff0000", "templates": ["gross", "drehleiter"] },
+            "B2": { "category": "Brand" },
+            "B2 Y": { "title": "Brand mit Menschenleben in Gefahr", "templates": ["rettung"] },
+        }))
+        .unwrap();
+        StichwortCatalogue::new(&stichwoerter).unwrap()
+    }
+
+    fn apply(stichwort: &str, title: &str, text: &str) -> Alarm {
+        let mut alarm = Alarm::new();
+        alarm.stichwort = stichwort.to_string();
+        alarm.title = title.to_string();
+        alarm.text = text.to_string();
+        catalogue().apply(&mut alarm);
+        alarm
+    }
+
+    fn keyword(alarm: &Alarm) -> Option<&str> {
+        alarm.stichwort_info.as_ref().map(|info| info.keyword.as_str())
+    }
+
+    #[test]
+    fn whole_words_longest_first() {
+        assert_eq!(keyword(&apply("Brand 1", "", "")), Some("Brand 1"));
+        assert_eq!(keyword(&apply("BRAND  12", "", "")), Some("Brand 12"));
+        assert_eq!(keyword(&apply("", "", "Alarm: Brand 12 Lagerhalle")), Some("Brand 12"));
+        assert_eq!(keyword(&apply("", "", "Alarm: Brand 1, Mülltonne")), Some("Brand 1"));
+        assert_eq!(keyword(&apply("B2 Y - Wohnungsbrand", "", "")), Some("B2 Y"));
+        assert_eq!(keyword(&apply("B2 - Wohnungsbrand", "", "")), Some("B2"));
+        assert_eq!(keyword(&apply("", "", "Brand 123 und B22")), None);
+        assert_eq!(keyword(&apply("THL 1", "", "Brand 1")), None);
+    }
+
+    #[test]
+    fn category_priority_and_templates() {
+        let mut alarm = Alarm::new();
+        alarm.stichwort = "Brand 12".to_string();
+        alarm.add_template_name("gross".to_string());
+        catalogue().apply(&mut alarm);
+        let info = alarm.stichwort_info.as_ref().unwrap();
+        assert_eq!((info.title.as_str(), info.category.as_str(), info.color.as_str()), ("Großbrand", "Brand", "#ff0000"));
+        assert_eq!(info.priority, AlarmPriority::Loud);
+        assert_eq!(alarm.template_names, vec!["gross", "drehleiter"]);
+
+        let alarm = apply("", "", "Brand 1");
+        assert_eq!(alarm.stichwort_info.as_ref().unwrap().priority, AlarmPriority::Silent);
+        assert_eq!(alarm.stichwort, "Brand 1");
+        assert_eq!(alarm.template_names, vec!["klein"]);
+
+        let alarm = apply("B2", "", "");
+        let info = alarm.stichwort_info.as_ref().unwrap();
+        assert_eq!((info.title.as_str(), info.color.as_str()), ("B2", ""));
+    }
+
+    #[test]
+    fn only_generic_titles_are_replaced() {
+        assert_eq!(apply("Brand 1", "", "").title, "Kleinbrand");
+        assert_eq!(apply("Brand 1", "Einsatz", "").title, "Kleinbrand");
+        assert_eq!(apply("Brand 1", "brand 1", "").title, "Kleinbrand");
+        assert_eq!(apply("Brand 1", "Mülltonne brennt", "").title, "Mülltonne brennt");
+    }
+}
@@ -0,0 +1,277 @@
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::thread;
+use std::time::Duration;
+use log::{info, warn};
+use socket2::{SockRef, TcpKeepalive};
+
+/// Read timeout of the sockets, like the serial port: the frame timeout is
+/// checked between reads.
+const READ_TIMEOUT: Duration = Duration::from_millis(10);
+
+/// Poll interval of a followed file at its end.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Idle time before the first keepalive probe and the time between probes.
+/// A device server that lost power is detected after a few minutes instead
+/// of never, as it sends nothing between alarms.
+const KEEPALIVE_TIME: Duration = Duration::from_secs(60);
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// TCP connection of a serial device server. A closed connection is an
+/// error, so the source reconnects.
+pub struct TcpPort(TcpStream);
+
+impl TcpPort {
+    pub fn connect(address: &str) -> Result<Self, String> {
+        let addr = address
+            .to_socket_addrs()
+            .map_err(|e| format!("Invalid address {}: {}", address, e))?
+            .next()
+            .ok_or_else(|| format!("Address {} could not be resolved", address))?;
+        let stream = TcpStream::connect_timeout(&addr, Duration::from_secs(5))
+            .map_err(|e| format!("Failed to connect to {}: {}", address, e))?;
+        Self::from_stream(stream)
+    }
+
+    pub fn from_stream(stream: TcpStream) -> Result<Self, String> {
+        stream.set_read_timeout(Some(READ_TIMEOUT)).map_err(|e| e.to_string())?;
+        let keepalive = TcpKeepalive::new().with_time(KEEPALIVE_TIME).with_interval(KEEPALIVE_INTERVAL);
+        SockRef::from(&stream).set_tcp_keepalive(&keepalive).map_err(|e| format!("Failed to enable TCP keepalive: {}", e))?;
+        Ok(Self(stream))
+    }
+}
+
+impl Read for TcpPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.read(buf) {
+            Ok(0) => Err(io::Error::new(io::ErrorKind::ConnectionAborted, "connection closed")),
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Err(io::ErrorKind::TimedOut.into()),
+            result => result,
+        }
+    }
+}
+
+/// Listening socket for a device server in TCP client mode. Reads from the
+/// newest connection: a new connection replaces the current one (the device
+/// server reconnected after a power loss), a closed one is dropped and the
+/// port waits for the next. Waiting is not an error, so the source stays up.
+pub struct ListenerPort {
+    listener: TcpListener,
+    address: String,
+    connection: Option<TcpPort>,
+}
+
+impl ListenerPort {
+    pub fn bind(address: &str) -> Result<Self, String> {
+        let listener = TcpListener::bind(address).map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+        listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+        Ok(Self { listener, address: address.to_string(), connection: None })
+    }
+
+    /// Takes the newest pending connection, older pending ones are dropped.
+    fn accept(&mut self) -> io::Result<()> {
+        loop {
+            match self.listener.accept() {
+                Ok((stream, peer)) => {
+                    // accepted sockets inherit non-blocking on some platforms
+                    stream.set_nonblocking(false)?;
+                    match TcpPort::from_stream(stream) {
+                        Ok(port) => {
+                            if self.connection.replace(port).is_some() {
+                                info!("Neue Verbindung von {} an {}, vorherige Verbindung getrennt", peer, self.address);
+                            } else {
+                                info!("Verbindung von {} an {}", peer, self.address);
+                            }
+                        }
+                        Err(e) => warn!("Connection from {} on {} dropped: {}", peer, self.address, e),
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(()),
+                Err(e) if e.kind() == io::ErrorKind::Interrupted || e.kind() == io::ErrorKind::ConnectionAborted => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Read for ListenerPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.accept()?;
+        let Some(connection) = &mut self.connection else {
+            thread::sleep(READ_TIMEOUT);
+            return Err(io::ErrorKind::TimedOut.into());
+        };
+        match connection.read(buf) {
+            Err(e) if e.kind() != io::ErrorKind::TimedOut => {
+                warn!("Verbindung an {} getrennt: {}, warte auf neue Verbindung", self.address, e);
+                self.connection = None;
+                Err(io::ErrorKind::TimedOut.into())
+            }
+            result => result,
+        }
+    }
+}
+
+/// Datagrams of a serial device server, every datagram is read as a whole.
+pub struct UdpPort(UdpSocket);
+
+impl UdpPort {
+    pub fn bind(address: &str) -> Result<Self, String> {
+        let socket = UdpSocket::bind(address).map_err(|e| format!("Failed to bind UDP {}: {}", address, e))?;
+        socket.set_read_timeout(Some(READ_TIMEOUT)).map_err(|e| e.to_string())?;
+        Ok(Self(socket))
+    }
+}
+
+impl Read for UdpPort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.0.recv(buf) {
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => Err(io::ErrorKind::TimedOut.into()),
+            result => result,
+        }
+    }
+}
+
+/// Output of a decoder like multimon-ng: a named pipe, a file or stdin (`-`).
+/// Read in a thread, so the frame timeout is checked while the pipe is
+/// idle. The end of a named pipe or stdin is an `UnexpectedEof` error; a
+/// regular file is followed from its end like `tail -f`, so old alarms in
+/// it are not sent again.
+pub struct PipePort {
+    chunks: flume::Receiver<io::Result<Vec<u8>>>,
+    rest: Vec<u8>,
+}
+
+impl PipePort {
+    pub fn open(path: &str) -> Result<Self, String> {
+        let (mut reader, follow): (Box<dyn Read + Send>, bool) = if path == "-" {
+            (Box::new(io::stdin()), false)
+        } else {
+            // opening a named pipe waits for the writer
+            let mut file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+            if is_fifo(&file) {
+                (Box::new(file), false)
+            } else {
+                file.seek(SeekFrom::End(0)).map_err(|e| format!("Failed to open {}: {}", path, e))?;
+                (Box::new(file), true)
+            }
+        };
+
+        let (send_chunks, chunks) = flume::bounded(16);
+        thread::spawn(move || {
+            let mut buffer = [0u8; 4096];
+            loop {
+                let chunk = match reader.read(&mut buffer) {
+                    Ok(0) if follow => {
+                        thread::sleep(FOLLOW_INTERVAL);
+                        continue;
+                    }
+                    Ok(0) => Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of stream")),
+                    Ok(bytes_read) => Ok(buffer[..bytes_read].to_vec()),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(e) => Err(e),
+                };
+                let end = chunk.is_err();
+                if send_chunks.send(chunk).is_err() || end {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self { chunks, rest: vec![] })
+    }
+}
+
+impl Read for PipePort {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.rest.is_empty() {
+            self.rest = match self.chunks.recv_timeout(READ_TIMEOUT) {
+                Ok(chunk) => chunk?,
+                Err(flume::RecvTimeoutError::Timeout) => return Err(io::ErrorKind::TimedOut.into()),
+                Err(flume::RecvTimeoutError::Disconnected) => {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "end of stream"))
+                }
+            };
+        }
+        let len = self.rest.len().min(buf.len());
+        buf[..len].copy_from_slice(&self.rest[..len]);
+        self.rest.drain(..len);
+        Ok(len)
+    }
+}
+
+#[cfg(unix)]
+fn is_fifo(file: &File) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    file.metadata().map(|metadata| metadata.file_type().is_fifo()).unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+fn is_fifo(_file: &File) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::time::Instant;
+
+    /// Reads until `expected` bytes arrived, timeouts are retried for up to 2 s.
+    fn read_bytes(port: &mut ListenerPort, expected: usize) -> Vec<u8> {
+        let mut data = vec![];
+        let start = Instant::now();
+        let mut buf = [0u8; 64];
+        while data.len() < expected && start.elapsed() < Duration::from_secs(2) {
+            match port.read(&mut buf) {
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+                Err(e) => assert_eq!(e.kind(), io::ErrorKind::TimedOut),
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn listener_waits_without_connection() {
+        let mut port = ListenerPort::bind("127.0.0.1:0").unwrap();
+        let mut buf = [0u8; 16];
+        assert_eq!(port.read(&mut buf).unwrap_err().kind(), io::ErrorKind::TimedOut);
+    }
+
+    #[test]
+    fn new_connection_replaces_the_old_one() {
+        let mut port = ListenerPort::bind("127.0.0.1:0").unwrap();
+        let address = port.listener.local_addr().unwrap();
+
+        let mut first = TcpStream::connect(address).unwrap();
+        first.write_all(b"1234567\r\n").unwrap();
+        assert_eq!(read_bytes(&mut port, 9), b"1234567\r\n");
+
+        let mut second = TcpStream::connect(address).unwrap();
+        second.write_all(b"7654321\r\n").unwrap();
+        assert_eq!(read_bytes(&mut port, 9), b"7654321\r\n");
+        // the old connection was closed by the port
+        let _ = first.write_all(b"0000000\r\n");
+        second.write_all(b"TH 1\r\n").unwrap();
+        assert_eq!(read_bytes(&mut port, 6), b"TH 1\r\n");
+    }
+
+    #[test]
+    fn closed_connection_waits_for_the_next() {
+        let mut port = ListenerPort::bind("127.0.0.1:0").unwrap();
+        let address = port.listener.local_addr().unwrap();
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(b"B3\r\n").unwrap();
+        assert_eq!(read_bytes(&mut port, 4), b"B3\r\n");
+        drop(client);
+        assert!(read_bytes(&mut port, 1).is_empty());
+        assert!(port.connection.is_none());
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(b"B4\r\n").unwrap();
+        assert_eq!(read_bytes(&mut port, 4), b"B4\r\n");
+    }
+}
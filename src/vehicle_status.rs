@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use chrono::Utc;
+use flume::{Receiver, Sender};
+use log::{debug, error, info};
+use crate::alarm::VehicleState;
+use crate::apis::divera_v2::DiveraV2;
+use crate::config::general::VehicleConfig;
+
+/// Text of the FMS status `1`-`9`.
+pub fn status_text(status: u8) -> &'static str {
+    match status {
+        1 => "Einsatzbereit über Funk",
+        2 => "Einsatzbereit auf Wache",
+        3 => "Einsatz übernommen",
+        4 => "Am Einsatzort",
+        5 => "Sprechwunsch",
+        6 => "Nicht einsatzbereit",
+        7 => "Patient aufgenommen",
+        8 => "Am Transportziel",
+        9 => "Fremdanmeldung",
+        _ => "",
+    }
+}
+
+/// Vehicle status `1`-`9` of a decoded FMS telegram, `None` for emergency
+/// (`0`) and the Leitstelle statuses `A`-`F`.
+pub fn parse_status(status: &str) -> Option<u8> {
+    match status.trim().parse::<u8>() {
+        Ok(status @ 1..=9) => Some(status),
+        _ => None,
+    }
+}
+
+/// FMS status of the vehicles by FMS id. Shared between the serial sources,
+/// the alarm handler and the health endpoint.
+#[derive(Clone, Default)]
+pub struct VehicleStatus {
+    vehicles: Arc<HashMap<String, VehicleConfig>>,
+    states: Arc<Mutex<HashMap<String, VehicleState>>>,
+    /// changes of vehicles with `divera_id`, see `sync_divera`
+    divera_changes: Option<Sender<(u64, VehicleState)>>,
+}
+
+impl VehicleStatus {
+    pub fn new(vehicles: HashMap<String, VehicleConfig>, divera_changes: Option<Sender<(u64, VehicleState)>>) -> Self {
+        Self {
+            vehicles: Arc::new(vehicles),
+            states: Arc::new(Mutex::new(HashMap::new())),
+            divera_changes,
+        }
+    }
+
+    /// Sets status `1`-`9` of a vehicle in `general.vehicles`, other FMS ids
+    /// are dropped. Returns `false` for an unknown vehicle or a repeated status.
+    pub fn update(&self, id: &str, status: u8, source: &str) -> bool {
+        let Some(vehicle) = self.vehicles.get(id) else {
+            debug!("FMS {}: Fahrzeug nicht konfiguriert, Status {} ignoriert", id, status);
+            return false;
+        };
+        let state = {
+            let mut states = self.states.lock().unwrap_or_else(|e| e.into_inner());
+            if states.get(id).is_some_and(|state| state.status == status) {
+                return false;
+            }
+            let state = VehicleState {
+                id: id.to_string(),
+                name: vehicle.name.clone(),
+                status,
+                status_text: status_text(status).to_string(),
+                available: matches!(status, 1 | 2),
+                source: source.to_string(),
+                since: Utc::now(),
+            };
+            states.insert(id.to_string(), state.clone());
+            state
+        };
+        info!("Fahrzeug {} Status {}: {}", state.name, status, state.status_text);
+
+        if let (Some(changes), Some(divera_id)) = (&self.divera_changes, vehicle.divera_id) {
+            if let Err(e) = changes.send((divera_id, state)) {
+                error!("Failed to queue vehicle status: {:?}", e);
+            }
+        }
+        true
+    }
+
+    /// All vehicles with a status, sorted by name.
+    pub fn snapshot(&self) -> Vec<VehicleState> {
+        let mut states: Vec<VehicleState> = self.states.lock().unwrap_or_else(|e| e.into_inner()).values().cloned().collect();
+        states.sort_by(|a, b| a.name.cmp(&b.name));
+        states
+    }
+}
+
+/// Sends the status changes to Divera, one request after the other so the
+/// order of the changes is kept.
+pub async fn sync_divera(changes: Receiver<(u64, VehicleState)>, divera: DiveraV2) {
+    while let Ok((divera_id, state)) = changes.recv_async().await {
+        match divera.set_vehicle_status(divera_id, state.status).await {
+            Ok(()) => info!("Divera: Status {} für {} gesetzt", state.status, state.name),
+            Err(e) => error!("Divera: Status {} für {} fehlgeschlagen: {}", state.status, state.name, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vehicles(divera_changes: Option<Sender<(u64, VehicleState)>>) -> VehicleStatus {
+        let vehicles = serde_json::from_value(serde_json::json!({
+            "0371721": { "name": "HLF 20", "divera_id": 42 },
+            "0371722": { "name": "DLK 23" },
+        }))
+        .unwrap();
+        VehicleStatus::new(vehicles, divera_changes)
+    }
+
+    #[test]
+    fn status_of_telegram() {
+        assert_eq!(parse_status("3"), Some(3));
+        assert_eq!(parse_status(" 9 "), Some(9));
+        assert_eq!(parse_status("0"), None);
+        assert_eq!(parse_status("A"), None);
+        assert_eq!(parse_status(""), None);
+        assert_eq!(status_text(4), "Am Einsatzort");
+        assert_eq!(status_text(0), "");
+    }
+
+    #[test]
+    fn table_keeps_configured_vehicles() {
+        let status = vehicles(None);
+        assert!(status.update("0371722", 2, "FMS"));
+        assert!(status.update("0371721", 3, "FMS"));
+        assert!(!status.update("0371721", 3, "FMS"));
+        assert!(!status.update("0379999", 1, "FMS"));
+
+        let table = status.snapshot();
+        let rows: Vec<(&str, &str, u8, bool)> = table.iter().map(|s| (s.id.as_str(), s.name.as_str(), s.status, s.available)).collect();
+        assert_eq!(rows, vec![("0371722", "DLK 23", 2, true), ("0371721", "HLF 20", 3, false)]);
+        assert_eq!(table[1].status_text, "Einsatz übernommen");
+        assert_eq!(table[1].source, "FMS");
+    }
+
+    #[test]
+    fn changes_of_divera_vehicles_are_queued() {
+        let (send, recv) = flume::unbounded();
+        let status = vehicles(Some(send));
+        status.update("0371721", 3, "FMS");
+        status.update("0371722", 6, "FMS");
+        status.update("0371721", 3, "FMS");
+        status.update("0371721", 4, "FMS");
+
+        let changes: Vec<(u64, u8)> = recv.try_iter().map(|(divera_id, state)| (divera_id, state.status)).collect();
+        assert_eq!(changes, vec![(42, 3), (42, 4)]);
+    }
+}